@@ -0,0 +1,146 @@
+use nnnoiseless::DenoiseState;
+use samplerate::{ConverterType, Samplerate};
+
+use crate::controller::errors::ProgramError;
+
+// rnnoise works on 48k audio only
+const DENOISE_RATE: u32 = 48000;
+// rnnoise expects samples of i16 scale
+const I16_SCALE: f32 = 32768.0;
+
+/// rnnoise denoiser of continuous interleaved audio, every channel is denoised separately,
+/// audio of other rates is resampled to 48k and back, output lags input by less than a frame
+pub struct Denoiser {
+    channels: usize,
+    states: Vec<Box<DenoiseState<'static>>>,
+    // 48k samples of every channel not filling a frame yet
+    pending: Vec<Vec<f32>>,
+    to_denoise_rate: Option<Samplerate>,
+    from_denoise_rate: Option<Samplerate>,
+}
+
+// resamplers are only used by the owner of denoiser
+unsafe impl Send for Denoiser {}
+
+impl Denoiser {
+    pub fn new(channels: u16, sample_rate: u32) -> Result<Self, ProgramError> {
+        if channels == 0 {
+            return Err(ProgramError::from(format!("unsupported input channel value: {}", channels)));
+        }
+        let channels = channels as usize;
+        let (to_denoise_rate, from_denoise_rate) = if sample_rate == DENOISE_RATE {
+            (None, None)
+        } else {
+            (Some(Samplerate::new(ConverterType::SincFastest, sample_rate, DENOISE_RATE, channels)?),
+             Some(Samplerate::new(ConverterType::SincFastest, DENOISE_RATE, sample_rate, channels)?))
+        };
+        Ok(Denoiser {
+            channels,
+            states: (0..channels).map(|_| DenoiseState::new()).collect(),
+            pending: vec![vec![]; channels],
+            to_denoise_rate,
+            from_denoise_rate,
+        })
+    }
+
+    pub fn process(&mut self, samples: &[f32]) -> Result<Vec<f32>, ProgramError> {
+        let input = match &self.to_denoise_rate {
+            Some(resampler) => resampler.process(samples)?,
+            None => samples.to_vec()
+        };
+        for (i, sample) in input.iter().enumerate() {
+            self.pending[i % self.channels].push(sample * I16_SCALE);
+        }
+
+        let frames = self.pending.iter().map(|pending| pending.len()).min().unwrap_or(0) / DenoiseState::FRAME_SIZE;
+        let mut output = Vec::with_capacity(frames * DenoiseState::FRAME_SIZE * self.channels);
+        let mut denoised = vec![[0f32; DenoiseState::FRAME_SIZE]; self.channels];
+        for frame in 0..frames {
+            let range = frame * DenoiseState::FRAME_SIZE..(frame + 1) * DenoiseState::FRAME_SIZE;
+            for (channel, state) in self.states.iter_mut().enumerate() {
+                state.process_frame(&mut denoised[channel], &self.pending[channel][range.clone()]);
+            }
+            for i in 0..DenoiseState::FRAME_SIZE {
+                for channel_denoised in &denoised {
+                    output.push(channel_denoised[i] / I16_SCALE);
+                }
+            }
+        }
+        for pending in &mut self.pending {
+            pending.drain(..frames * DenoiseState::FRAME_SIZE);
+        }
+
+        if output.is_empty() {
+            return Ok(output);
+        }
+        match &self.from_denoise_rate {
+            Some(resampler) => Ok(resampler.process(&output)?),
+            None => Ok(output)
+        }
+    }
+}
+
+/// denoiser turned on and off by a switch, used in audio callbacks which are unable to report errors,
+/// audio passes through unchanged if denoiser fails until it is switched off
+pub struct SwitchableDenoiser {
+    channels: u16,
+    sample_rate: u32,
+    denoiser: Option<Denoiser>,
+    failed: bool,
+}
+
+impl SwitchableDenoiser {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        SwitchableDenoiser {
+            channels,
+            sample_rate,
+            denoiser: None,
+            failed: false,
+        }
+    }
+
+    fn try_process(&mut self, samples: &[f32]) -> Result<Vec<f32>, ProgramError> {
+        let denoiser = match &mut self.denoiser {
+            Some(denoiser) => denoiser,
+            None => self.denoiser.insert(Denoiser::new(self.channels, self.sample_rate)?)
+        };
+        denoiser.process(samples)
+    }
+
+    pub fn process(&mut self, enabled: bool, samples: Vec<f32>) -> Vec<f32> {
+        if !enabled {
+            // state of previous audio is useless once denoising stops
+            self.denoiser = None;
+            self.failed = false;
+            return samples;
+        }
+        if self.failed {
+            return samples;
+        }
+        match self.try_process(&samples) {
+            Ok(denoised) => denoised,
+            Err(err) => {
+                log::error!("Failed to denoise audio, pass through instead, err: {}", err);
+                self.denoiser = None;
+                self.failed = true;
+                samples
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denoise_by_frames() {
+        let mut denoiser = Denoiser::new(2, DENOISE_RATE).unwrap();
+        // 700 stereo samples fill one frame and 220 samples are kept
+        let output = denoiser.process(&vec![0.1f32; 700 * 2]).unwrap();
+        assert_eq!(output.len(), DenoiseState::FRAME_SIZE * 2);
+        let output = denoiser.process(&vec![0.1f32; 300 * 2]).unwrap();
+        assert_eq!(output.len(), DenoiseState::FRAME_SIZE * 2);
+        assert_eq!(denoiser.pending[0].len(), 40);
+    }
+}
@@ -6,12 +6,20 @@ use cpal::traits::{DeviceTrait, StreamTrait};
 use lazy_static::lazy_static;
 use tokio::sync::Mutex as AsyncMutex;
 
+use crate::audio::denoise::SwitchableDenoiser;
+use crate::audio::loudness::Agc;
+use crate::config::voice_recognition::TalkParams;
 use crate::controller::errors::{CommonError, ProgramError};
 
+/// length of audio kept by listener, utterances can't be longer than it
+pub const BUFFER_MS: u32 = 30 * 1000;
+
 lazy_static! {
     static ref STREAM_AUDIO: Arc<Mutex<StreamAudio>> = Arc::new(Mutex::new(StreamAudio::new()));
     static ref RUNNING: AtomicBool = AtomicBool::new(false);
-    pub static ref LISTENER: Arc<AsyncMutex<Listener>> = Arc::new(AsyncMutex::new(Listener::new(30 * 1000)));
+    static ref DENOISE: AtomicBool = AtomicBool::new(false);
+    static ref AGC: AtomicBool = AtomicBool::new(false);
+    pub static ref LISTENER: Arc<AsyncMutex<Listener>> = Arc::new(AsyncMutex::new(Listener::new(BUFFER_MS)));
 }
 
 pub struct StreamAudio {
@@ -19,6 +27,8 @@ pub struct StreamAudio {
     m_audio_new: Vec<f32>,
     m_audio_pos: usize,
     m_audio_len: usize,
+    // samples written since the stream started, used as absolute position of the latest sample
+    m_audio_total: u64,
 }
 
 impl StreamAudio {
@@ -28,6 +38,7 @@ impl StreamAudio {
             m_audio_new: vec![],
             m_audio_pos: 0,
             m_audio_len: 0,
+            m_audio_total: 0,
         }
     }
 
@@ -40,10 +51,12 @@ impl StreamAudio {
         self.reset();
         self.m_audio.clear();
         self.m_audio_new.clear();
+        self.m_audio_total = 0;
     }
 
     pub fn on_data(&mut self, samples: Vec<f32>) {
         let n_samples = samples.len();
+        self.m_audio_total += n_samples as u64;
 
         self.m_audio_new.resize(n_samples, 0.0);
 
@@ -64,6 +77,29 @@ impl StreamAudio {
             self.m_audio_len = self.m_audio_len + n_samples.min(self.m_audio.len() - self.m_audio_len);
         }
     }
+
+    /// copy samples of absolute range \[from, to) still kept in buffer into result,
+    /// returns absolute position of the first copied sample
+    pub fn copy_range(&self, from: u64, to: u64, result: &mut Vec<f32>) -> u64 {
+        let oldest = self.m_audio_total - self.m_audio_len as u64;
+        let from = from.max(oldest).min(self.m_audio_total);
+        let to = to.max(from).min(self.m_audio_total);
+        let n_samples = (to - from) as usize;
+        result.clear();
+        if n_samples == 0 {
+            return from;
+        }
+        let len = self.m_audio.len();
+        let back = (self.m_audio_total - from) as usize;
+        let s0 = (self.m_audio_pos + len - back) % len;
+        if s0 + n_samples > len {
+            result.extend_from_slice(&self.m_audio[s0..]);
+            result.extend_from_slice(&self.m_audio[..n_samples - (len - s0)]);
+        } else {
+            result.extend_from_slice(&self.m_audio[s0..s0 + n_samples]);
+        }
+        from
+    }
 }
 
 unsafe impl Send for StreamAudio {}
@@ -105,11 +141,19 @@ impl Listener {
             buffer_size: BufferSize::Default,
         };
 
+        let mut denoiser = SwitchableDenoiser::new(config.channels(), config.sample_rate().0);
+        let mut agc = Agc::new(config.channels(), config.sample_rate().0);
         let stream = device.build_input_stream(
             &stream_config,
-            |data: &[T], _: &_| {
+            move |data: &[T], _: &_| {
                 if RUNNING.load(Ordering::SeqCst) {
                     let buffer: Vec<f32> = data.iter().map(|s| T::to_sample(*s)).collect();
+                    let mut buffer = denoiser.process(DENOISE.load(Ordering::Acquire), buffer);
+                    if AGC.load(Ordering::Acquire) {
+                        agc.process(&mut buffer);
+                    } else {
+                        agc.reset();
+                    }
                     let lock = STREAM_AUDIO.clone();
                     let mut stream_audio = lock.lock().unwrap();
                     stream_audio.on_data(buffer);
@@ -243,6 +287,38 @@ impl Listener {
     }
 }
 
+/// denoise captured audio before it is buffered, applies to a running stream
+pub fn set_denoise(denoise: bool) {
+    DENOISE.store(denoise, Ordering::Release);
+}
+
+/// apply automatic gain control to captured audio after denoising, applies to a running stream
+pub fn set_agc(agc: bool) {
+    AGC.store(agc, Ordering::Release);
+}
+
+impl Listener {
+    /// max length of audio kept in buffer
+    pub fn len_ms(&self) -> u32 {
+        self.m_len_ms
+    }
+
+    /// absolute position after the latest sample, see [StreamAudio::copy_range]
+    pub fn position(&self) -> u64 {
+        let lock = STREAM_AUDIO.clone();
+        let stream_audio = lock.lock().unwrap();
+        stream_audio.m_audio_total
+    }
+
+    /// get samples of absolute range \[from, to), returns absolute position of the first sample got,
+    /// which is after `from` if part of the range is overwritten or cleared
+    pub fn get_range(&self, from: u64, to: u64, result: &mut Vec<f32>) -> u64 {
+        let lock = STREAM_AUDIO.clone();
+        let stream_audio = lock.lock().unwrap();
+        stream_audio.copy_range(from, to, result)
+    }
+}
+
 impl Drop for Listener {
     fn drop(&mut self) {
         log::debug!("Listener dropped");
@@ -254,7 +330,7 @@ impl Drop for Listener {
     }
 }
 
-fn high_pass_filter(data: &mut Vec<f32>, cutoff: f32, sample_rate: f32) {
+pub fn high_pass_filter(data: &mut Vec<f32>, cutoff: f32, sample_rate: f32) {
     let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
     let dt = 1.0 / sample_rate;
     let alpha = dt / (rc + dt);
@@ -289,3 +365,148 @@ pub fn vad_simple(pcmf32: &mut Vec<f32>, sample_rate: u32, last_ms: u32, vad_tho
 
     energy_last <= vad_thold * energy_all
 }
+
+
+/// splits audio into utterances by speech flags of consecutive vad frames, an utterance starts
+/// at the first speech frame with `pre_roll_ms` of audio before it and ends after `hangover_ms`
+/// of silence, speech longer than `voice_ms` is split into several utterances
+pub struct UtteranceSegmenter {
+    // absolute position of the first frame
+    origin: u64,
+    channels: u16,
+    sample_rate: u32,
+    frame_ms: u32,
+    // frames pushed
+    frames: u64,
+    // frame current utterance starts at
+    start: Option<u64>,
+    speech_frames: u64,
+    silent_frames: u64,
+    // frame last utterance ends at, pre-roll never reaches before it
+    last_end: u64,
+}
+
+impl UtteranceSegmenter {
+    pub fn new(origin: u64, channels: u16, sample_rate: u32, frame_ms: u32) -> Self {
+        UtteranceSegmenter {
+            origin,
+            channels,
+            sample_rate,
+            frame_ms,
+            frames: 0,
+            start: None,
+            speech_frames: 0,
+            silent_frames: 0,
+            last_end: 0,
+        }
+    }
+
+    pub fn in_speech(&self) -> bool {
+        self.start.is_some()
+    }
+
+    fn ms_to_frames(&self, ms: u32) -> u64 {
+        (ms / self.frame_ms) as u64
+    }
+
+    /// absolute position of frame start, calculated from time so that frames do not drift
+    fn frame_position(&self, frame: u64) -> u64 {
+        self.origin + frame * self.frame_ms as u64 * self.sample_rate as u64 / 1000 * self.channels as u64
+    }
+
+    fn finish(&mut self, end: u64, pre_roll_ms: u32, min_speech_ms: u32) -> Option<(u64, u64)> {
+        let start = self.start.take()?;
+        let speech_frames = self.speech_frames;
+        self.speech_frames = 0;
+        self.silent_frames = 0;
+        if speech_frames < self.ms_to_frames(min_speech_ms).max(1) {
+            // too short to be speech, e.g. a click
+            return None;
+        }
+        let from = start.saturating_sub(self.ms_to_frames(pre_roll_ms)).max(self.last_end);
+        self.last_end = end;
+        Some((self.frame_position(from), self.frame_position(end)))
+    }
+
+    /// push speech flag of next frame, returns absolute range of an utterance if one ends
+    pub fn push(&mut self, speech: bool, params: &TalkParams) -> Option<(u64, u64)> {
+        let frame = self.frames;
+        self.frames += 1;
+        match self.start {
+            None => {
+                if speech {
+                    self.start = Some(frame);
+                    self.speech_frames = 1;
+                    self.silent_frames = 0;
+                }
+                None
+            }
+            Some(start) => {
+                if speech {
+                    self.speech_frames += 1;
+                    self.silent_frames = 0;
+                } else {
+                    self.silent_frames += 1;
+                }
+                if self.silent_frames >= self.ms_to_frames(params.hangover_ms).max(1) {
+                    return self.finish(self.frames, params.pre_roll_ms, params.min_speech_ms);
+                }
+                if self.frames - start >= self.ms_to_frames(params.voice_ms).max(1) {
+                    // split long speech and continue with a new utterance from next frame
+                    let continued = self.silent_frames == 0;
+                    let utterance = self.finish(self.frames, params.pre_roll_ms, params.min_speech_ms);
+                    if continued {
+                        self.start = Some(self.frames);
+                        self.last_end = self.frames;
+                    }
+                    return utterance;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_range() {
+        let mut stream_audio = StreamAudio::new();
+        stream_audio.m_audio.resize(8, 0.0);
+        stream_audio.on_data((0..6).map(|i| i as f32).collect());
+        stream_audio.on_data((6..11).map(|i| i as f32).collect());
+
+        let mut result = vec![];
+        // buffer keeps samples 3..11 and wraps around
+        assert_eq!(stream_audio.copy_range(5, 10, &mut result), 5);
+        assert_eq!(result, vec![5.0, 6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(stream_audio.copy_range(0, 5, &mut result), 3);
+        assert_eq!(result, vec![3.0, 4.0]);
+        assert_eq!(stream_audio.copy_range(11, 20, &mut result), 11);
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_utterance_segmenter() {
+        let params = TalkParams {
+            pre_roll_ms: 20,
+            hangover_ms: 30,
+            min_speech_ms: 20,
+            voice_ms: 100,
+            ..TalkParams::default()
+        };
+        // 10ms frames of 1000Hz mono audio are 10 samples
+        let mut segmenter = UtteranceSegmenter::new(1000, 1, 1000, 10);
+        let mut push = |flags: &str| -> Vec<(u64, u64)> {
+            flags.chars().filter_map(|flag| segmenter.push(flag == '1', &params)).collect()
+        };
+        // a click shorter than min speech is dropped
+        assert_eq!(push("00001000"), vec![]);
+        // speech of frames 10..15 with a short pause, pre-roll of 2 frames and hangover of 3 frames
+        assert_eq!(push("0011011000"), vec![(1080, 1180)]);
+        // long speech is split by max utterance length of 10 frames, pre-roll stops at last utterance
+        assert_eq!(push("111111111111000"), vec![(1180, 1280), (1280, 1330)]);
+    }
+}
@@ -0,0 +1,268 @@
+use std::io::Cursor;
+
+use crate::controller::errors::ProgramError;
+
+// agc drives speech towards -20dBFS rms
+const AGC_TARGET_RMS: f32 = 0.1;
+// +20dB at most so that quiet microphones are lifted without pumping up the noise floor too much
+const AGC_MAX_GAIN: f32 = 10.0;
+const AGC_MIN_GAIN: f32 = 0.1;
+// blocks quieter than about -50dBFS are regarded as silence and keep current gain
+const AGC_GATE_RMS: f32 = 0.003;
+// gain drops fast on loud input and rises slowly on quiet input
+const AGC_ATTACK_MS: f32 = 10.0;
+const AGC_RELEASE_MS: f32 = 1000.0;
+const AGC_BLOCK_MS: u32 = 10;
+
+// loudness of bs.1770 is measured on blocks of 400ms overlapping by 75%
+const LOUDNESS_BLOCK_MS: u32 = 400;
+const LOUDNESS_STEP_MS: u32 = 100;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// automatic gain control of continuous interleaved audio
+pub struct Agc {
+    channels: usize,
+    sample_rate: u32,
+    gain: f32,
+}
+
+impl Agc {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        Agc {
+            channels: (channels as usize).max(1),
+            sample_rate,
+            gain: 1.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+
+    /// gain of every block follows rms of the block, it changes linearly within a block to avoid clicks
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let block_frames = (self.sample_rate * AGC_BLOCK_MS / 1000).max(1) as usize;
+        for block in samples.chunks_mut(block_frames * self.channels) {
+            let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+            let gain = if rms > AGC_GATE_RMS {
+                let desired = (AGC_TARGET_RMS / rms).clamp(AGC_MIN_GAIN, AGC_MAX_GAIN);
+                let time_ms = if desired < self.gain { AGC_ATTACK_MS } else { AGC_RELEASE_MS };
+                let block_ms = (block.len() / self.channels) as f32 * 1000.0 / self.sample_rate as f32;
+                self.gain + (desired - self.gain) * (1.0 - (-block_ms / time_ms).exp())
+            } else {
+                self.gain
+            };
+            let frames = (block.len() / self.channels).max(1);
+            let step = (gain - self.gain) / frames as f32;
+            for (i, frame) in block.chunks_mut(self.channels).enumerate() {
+                let frame_gain = self.gain + step * (i + 1) as f32;
+                for sample in frame {
+                    *sample = (*sample * frame_gain).clamp(-1.0, 1.0);
+                }
+            }
+            self.gain = gain;
+        }
+    }
+}
+
+/// biquad filter of direct form 1
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [1.0, a[1] / a[0], a[2] / a[0]],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0] - self.a[2] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// k-weighting filter of bs.1770, a high shelf modelling the head followed by a high pass,
+/// coefficients are derived for any sample rate like libebur128 and match the spec at 48k
+fn k_weighting(sample_rate: u32) -> (Biquad, Biquad) {
+    let sample_rate = sample_rate as f64;
+
+    let (freq, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * freq / sample_rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::new(
+        [vh + vb * k / q + k * k, 2.0 * (k * k - vh), vh - vb * k / q + k * k],
+        [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k]);
+
+    let (freq, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * freq / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    // numerator of the high pass is not normalized by a0
+    let high_pass = Biquad::new(
+        [a0, -2.0 * a0, a0],
+        [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k]);
+    (shelf, high_pass)
+}
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// integrated loudness in LUFS of interleaved audio by bs.1770 with gating, every channel is weighted
+/// as a front channel, none if audio is silent, audio shorter than a block is measured as one block
+pub fn integrated_loudness(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f64> {
+    let channels = (channels as usize).max(1);
+    let frames = samples.len() / channels;
+    if frames == 0 || sample_rate == 0 {
+        return None;
+    }
+
+    // k-weighted squares of every frame summed over channels
+    let mut squares = vec![0f64; frames];
+    for channel in 0..channels {
+        let (mut shelf, mut high_pass) = k_weighting(sample_rate);
+        for (frame, square) in squares.iter_mut().enumerate() {
+            let y = high_pass.process(shelf.process(samples[frame * channels + channel] as f64));
+            *square += y * y;
+        }
+    }
+
+    let block = (sample_rate * LOUDNESS_BLOCK_MS / 1000) as usize;
+    let step = (sample_rate * LOUDNESS_STEP_MS / 1000) as usize;
+    let powers: Vec<f64> = if frames <= block {
+        vec![squares.iter().sum::<f64>() / frames as f64]
+    } else {
+        (0..=(frames - block) / step)
+            .map(|i| squares[i * step..i * step + block].iter().sum::<f64>() / block as f64)
+            .collect()
+    };
+
+    let gated_mean = |gate: f64| -> Option<f64> {
+        let gated: Vec<f64> = powers.iter().copied()
+            .filter(|power| *power > 0.0 && block_loudness(*power) > gate)
+            .collect();
+        if gated.is_empty() {
+            None
+        } else {
+            Some(gated.iter().sum::<f64>() / gated.len() as f64)
+        }
+    };
+    let absolute_gated = gated_mean(ABSOLUTE_GATE_LUFS)?;
+    let relative_gate = (block_loudness(absolute_gated) + RELATIVE_GATE_LU).max(ABSOLUTE_GATE_LUFS);
+    gated_mean(relative_gate).map(block_loudness)
+}
+
+/// gain bringing audio to target loudness, limited so that sample peak stays below `peak_db`,
+/// none if audio is silent
+pub fn normalize_gain(samples: &[f32], channels: u16, sample_rate: u32, target_lufs: f32, peak_db: f32) -> Option<f32> {
+    let loudness = integrated_loudness(samples, channels, sample_rate)?;
+    let gain = 10f64.powf((target_lufs as f64 - loudness) / 20.0) as f32;
+    let peak = samples.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+    if peak <= 0.0 {
+        return None;
+    }
+    let max_gain = 10f32.powf(peak_db / 20.0) / peak;
+    Some(gain.min(max_gain))
+}
+
+/// normalize loudness of wav file bytes, format of the file is kept, silent audio is returned as is
+pub fn normalize_wav(wav: &[u8], target_lufs: f32, peak_db: f32) -> Result<Vec<u8>, ProgramError> {
+    let mut reader = hound::WavReader::new(Cursor::new(wav))?;
+    let spec = reader.spec();
+    let int_scale = 2f32.powi(spec.bits_per_sample as i32 - 1);
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => reader.samples::<i32>()
+            .map(|sample| sample.map(|sample| sample as f32 / int_scale))
+            .collect::<Result<_, _>>()?,
+    };
+    let gain = match normalize_gain(&samples, spec.channels, spec.sample_rate, target_lufs, peak_db) {
+        Some(gain) => gain,
+        None => return Ok(wav.to_vec())
+    };
+    log::debug!("Normalize wav to {} LUFS by gain {}", target_lufs, gain);
+
+    let mut cursor = Cursor::new(Vec::with_capacity(wav.len()));
+    {
+        let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+        for sample in samples {
+            match spec.sample_format {
+                hound::SampleFormat::Float => writer.write_sample(sample * gain)?,
+                hound::SampleFormat::Int => {
+                    let sample = (sample * gain * int_scale).round().clamp(-int_scale, int_scale - 1.0);
+                    writer.write_sample(sample as i32)?
+                }
+            }
+        }
+        writer.finalize()?;
+    }
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, sample_rate: u32, seconds: u32) -> Vec<f32> {
+        (0..sample_rate * seconds)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * 997.0 * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_integrated_loudness() {
+        // a full scale 997Hz sine of one channel is -3.01 LUFS by bs.1770, so -20dB of it is -23.01
+        let loudness = integrated_loudness(&sine(0.1, 48000, 3), 1, 48000).unwrap();
+        assert!((loudness + 23.01).abs() < 0.05, "loudness: {}", loudness);
+        let loudness = integrated_loudness(&sine(0.1, 44100, 3), 1, 44100).unwrap();
+        assert!((loudness + 23.01).abs() < 0.05, "loudness: {}", loudness);
+        assert_eq!(integrated_loudness(&vec![0.0; 48000], 1, 48000), None);
+    }
+
+    #[test]
+    fn test_normalize_wav() {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 24000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut cursor = Cursor::new(vec![]);
+        {
+            let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+            for sample in sine(0.05, 24000, 2) {
+                writer.write_sample((sample * 32768.0) as i16).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+        let normalized = normalize_wav(&cursor.into_inner(), -16.0, -1.0).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(normalized)).unwrap();
+        assert_eq!(reader.spec(), spec);
+        let samples: Vec<f32> = reader.samples::<i16>().map(|s| s.unwrap() as f32 / 32768.0).collect();
+        let loudness = integrated_loudness(&samples, 1, 24000).unwrap();
+        assert!((loudness + 16.0).abs() < 0.1, "loudness: {}", loudness);
+    }
+
+    #[test]
+    fn test_agc_lifts_quiet_input() {
+        let mut agc = Agc::new(1, 16000);
+        let mut samples = sine(0.05, 16000, 5);
+        agc.process(&mut samples);
+        let tail = &samples[samples.len() - 1600..];
+        let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+        assert!((rms - AGC_TARGET_RMS).abs() < 0.01, "rms: {}", rms);
+    }
+}
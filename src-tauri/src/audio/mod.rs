@@ -1,2 +1,6 @@
+pub mod denoise;
 pub mod listener;
-pub mod talk;
\ No newline at end of file
+pub mod loudness;
+pub mod silero;
+pub mod talk;
+pub mod vad;
\ No newline at end of file
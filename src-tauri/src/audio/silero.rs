@@ -0,0 +1,492 @@
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::controller::errors::ProgramError;
+
+const VAD_DIR: &str = "vad";
+const MODEL_FILE_NAME: &str = "silero_vad.onnx";
+#[cfg(target_os = "windows")]
+const LIBRARY_FILE_NAME: &str = "onnxruntime.dll";
+#[cfg(target_os = "macos")]
+const LIBRARY_FILE_NAME: &str = "libonnxruntime.dylib";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARY_FILE_NAME: &str = "libonnxruntime.so";
+
+/// silero vad v4 and v5 accept 16k audio in frames of 512 samples
+pub const SILERO_SAMPLE_RATE: u32 = 16000;
+pub const SILERO_FRAME_SIZE: usize = 512;
+// size of lstm state, `h` and `c` of v4 are [2, 1, 64] each, `state` of v5 is [2, 1, 128]
+const STATE_SIZE: usize = 2 * 2 * 64;
+// v5 takes last samples of previous frame in front of every frame
+const CONTEXT_SIZE: usize = 64;
+
+// oldest api version providing every function used below, newer runtimes keep serving it
+const ORT_API_VERSION: u32 = 11;
+
+// indices of used functions in `OrtApi` of onnxruntime_c_api.h, the struct only grows by appending
+const ORT_GET_ERROR_MESSAGE: usize = 2;
+const ORT_CREATE_ENV: usize = 3;
+const ORT_CREATE_SESSION: usize = 7;
+const ORT_RUN: usize = 9;
+const ORT_CREATE_SESSION_OPTIONS: usize = 10;
+const ORT_SET_INTRA_OP_NUM_THREADS: usize = 24;
+const ORT_SET_INTER_OP_NUM_THREADS: usize = 25;
+const ORT_SESSION_GET_INPUT_COUNT: usize = 30;
+const ORT_SESSION_GET_INPUT_NAME: usize = 36;
+const ORT_CREATE_TENSOR_WITH_DATA_AS_ORT_VALUE: usize = 49;
+const ORT_GET_TENSOR_MUTABLE_DATA: usize = 51;
+const ORT_CREATE_CPU_MEMORY_INFO: usize = 69;
+const ORT_ALLOCATOR_FREE: usize = 76;
+const ORT_GET_ALLOCATOR_WITH_DEFAULT_OPTIONS: usize = 78;
+const ORT_RELEASE_ENV: usize = 92;
+const ORT_RELEASE_STATUS: usize = 93;
+const ORT_RELEASE_MEMORY_INFO: usize = 94;
+const ORT_RELEASE_SESSION: usize = 95;
+const ORT_RELEASE_VALUE: usize = 96;
+const ORT_RELEASE_SESSION_OPTIONS: usize = 100;
+
+const ORT_LOGGING_LEVEL_WARNING: c_int = 2;
+const ORT_ARENA_ALLOCATOR: c_int = 1;
+const ORT_MEM_TYPE_DEFAULT: c_int = 0;
+const ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT: c_int = 1;
+const ONNX_TENSOR_ELEMENT_DATA_TYPE_INT64: c_int = 7;
+
+type OrtStatusPtr = *mut c_void;
+
+#[cfg(target_os = "windows")]
+type OrtChar = u16;
+#[cfg(not(target_os = "windows"))]
+type OrtChar = c_char;
+
+#[repr(C)]
+struct OrtApiBase {
+    get_api: unsafe extern "system" fn(u32) -> *const *const c_void,
+    get_version_string: unsafe extern "system" fn() -> *const c_char,
+}
+
+lazy_static! {
+    static ref SILERO_MODEL: Arc<Mutex<SileroModelHolder>> = Arc::new(Mutex::new(SileroModelHolder::default()));
+}
+
+/// lstm state carried between frames, continuous audio fed in several calls should share one state
+pub struct SileroState {
+    lstm: [f32; STATE_SIZE],
+    context: [f32; CONTEXT_SIZE],
+}
+
+impl Default for SileroState {
+    fn default() -> Self {
+        SileroState {
+            lstm: [0f32; STATE_SIZE],
+            context: [0f32; CONTEXT_SIZE],
+        }
+    }
+}
+
+/// released versions of silero vad model differ in inputs and outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SileroVersion {
+    // inputs `input`, `sr`, `h`, `c`, outputs `output`, `hn`, `cn`
+    V4,
+    // inputs `input`, `state`, `sr`, outputs `output`, `stateN`
+    V5,
+}
+
+impl SileroVersion {
+    /// version of model by its input names
+    fn detect(input_names: &[String]) -> Result<Self, ProgramError> {
+        let has = |name: &str| input_names.iter().any(|input| input == name);
+        if has("input") && has("sr") && has("h") && has("c") {
+            Ok(SileroVersion::V4)
+        } else if has("input") && has("sr") && has("state") {
+            Ok(SileroVersion::V5)
+        } else {
+            Err(ProgramError::from(format!(
+                "Unsupported silero vad model with inputs [{}], expect inputs of v4 (input, sr, h, c) or v5 (input, state, sr)",
+                input_names.join(", "))))
+        }
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        match self {
+            SileroVersion::V4 => &["output", "hn", "cn"],
+            SileroVersion::V5 => &["output", "stateN"],
+        }
+    }
+}
+
+/// loaded model with its paths, or the error of last load so a broken setup is not reloaded on every call
+#[derive(Default)]
+struct SileroModelHolder {
+    paths: Option<(PathBuf, PathBuf)>,
+    model: Option<SileroModel>,
+    error: Option<String>,
+}
+
+/// silero vad model run by onnxruntime library on cpu
+struct SileroModel {
+    _lib: libloading::Library,
+    api: *const *const c_void,
+    env: *mut c_void,
+    session: *mut c_void,
+    memory_info: *mut c_void,
+    version: SileroVersion,
+}
+
+unsafe impl Send for SileroModel {}
+
+fn model_file(model_path: Option<&str>) -> PathBuf {
+    match model_path.map(str::trim).filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(VAD_DIR).join(MODEL_FILE_NAME)
+    }
+}
+
+fn library_file(library_path: Option<&str>) -> PathBuf {
+    match library_path.map(str::trim).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if path.is_dir() {
+                path.join(LIBRARY_FILE_NAME)
+            } else {
+                path
+            }
+        }
+        None => PathBuf::from(VAD_DIR).join(LIBRARY_FILE_NAME)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn to_ort_path(path: &Path) -> Result<Vec<OrtChar>, ProgramError> {
+    use std::os::windows::ffi::OsStrExt;
+    Ok(path.as_os_str().encode_wide().chain(std::iter::once(0)).collect())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn to_ort_path(path: &Path) -> Result<Vec<OrtChar>, ProgramError> {
+    let path = CString::new(path.to_string_lossy().as_bytes())
+        .map_err(|_| ProgramError::from("unable to convert String to CString"))?;
+    Ok(path.as_bytes_with_nul().iter().map(|b| *b as OrtChar).collect())
+}
+
+impl SileroModel {
+    fn load(library_file: &Path, model_file: &Path) -> Result<Self, ProgramError> {
+        if !model_file.is_file() {
+            return Err(ProgramError::from(format!("Silero vad model {} not found", model_file.to_string_lossy())));
+        }
+        let lib = unsafe {
+            libloading::Library::new(library_file)?
+        };
+        let api = unsafe {
+            let get_api_base: libloading::Symbol<unsafe extern "system" fn() -> *const OrtApiBase> =
+                lib.get(b"OrtGetApiBase\0")?;
+            let api_base = &*get_api_base();
+            log::debug!("Load onnxruntime {}", CStr::from_ptr((api_base.get_version_string)()).to_string_lossy());
+            (api_base.get_api)(ORT_API_VERSION)
+        };
+        if api.is_null() {
+            return Err(ProgramError::from(format!("onnxruntime library does not support api version {}", ORT_API_VERSION)));
+        }
+        let mut model = SileroModel {
+            _lib: lib,
+            api,
+            env: std::ptr::null_mut(),
+            session: std::ptr::null_mut(),
+            memory_info: std::ptr::null_mut(),
+            version: SileroVersion::V4,
+        };
+        // pointers created so far are released by drop if any step fails
+        unsafe {
+            model.init(model_file)?;
+        }
+        Ok(model)
+    }
+
+    unsafe fn func<T>(&self, index: usize) -> T {
+        std::mem::transmute_copy(&*self.api.add(index))
+    }
+
+    unsafe fn check(&self, status: OrtStatusPtr) -> Result<(), ProgramError> {
+        if status.is_null() {
+            return Ok(());
+        }
+        let get_error_message: unsafe extern "system" fn(OrtStatusPtr) -> *const c_char = self.func(ORT_GET_ERROR_MESSAGE);
+        let release_status: unsafe extern "system" fn(OrtStatusPtr) = self.func(ORT_RELEASE_STATUS);
+        let message = CStr::from_ptr(get_error_message(status)).to_string_lossy().to_string();
+        release_status(status);
+        Err(ProgramError::from(format!("onnxruntime error: {}", message)))
+    }
+
+    unsafe fn init(&mut self, model_file: &Path) -> Result<(), ProgramError> {
+        let create_env: unsafe extern "system" fn(c_int, *const c_char, *mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_CREATE_ENV);
+        let log_id = CString::new("silero_vad").unwrap();
+        let status = create_env(ORT_LOGGING_LEVEL_WARNING, log_id.as_ptr(), &mut self.env);
+        self.check(status)?;
+
+        let create_cpu_memory_info: unsafe extern "system" fn(c_int, c_int, *mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_CREATE_CPU_MEMORY_INFO);
+        let status = create_cpu_memory_info(ORT_ARENA_ALLOCATOR, ORT_MEM_TYPE_DEFAULT, &mut self.memory_info);
+        self.check(status)?;
+
+        let create_session_options: unsafe extern "system" fn(*mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_CREATE_SESSION_OPTIONS);
+        let set_intra_op_num_threads: unsafe extern "system" fn(*mut c_void, c_int) -> OrtStatusPtr =
+            self.func(ORT_SET_INTRA_OP_NUM_THREADS);
+        let set_inter_op_num_threads: unsafe extern "system" fn(*mut c_void, c_int) -> OrtStatusPtr =
+            self.func(ORT_SET_INTER_OP_NUM_THREADS);
+        let release_session_options: unsafe extern "system" fn(*mut c_void) = self.func(ORT_RELEASE_SESSION_OPTIONS);
+        let create_session: unsafe extern "system" fn(*const c_void, *const OrtChar, *const c_void, *mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_CREATE_SESSION);
+
+        let mut options: *mut c_void = std::ptr::null_mut();
+        self.check(create_session_options(&mut options))?;
+        // the model is tiny, a single thread is faster than scheduling among threads
+        let result = self.check(set_intra_op_num_threads(options, 1))
+            .and_then(|_| self.check(set_inter_op_num_threads(options, 1)))
+            .and_then(|_| to_ort_path(model_file));
+        let result = match result {
+            Ok(model_path) => {
+                let mut session: *mut c_void = std::ptr::null_mut();
+                let status = create_session(self.env, model_path.as_ptr(), options, &mut session);
+                self.session = session;
+                self.check(status)
+            }
+            Err(err) => Err(err)
+        };
+        release_session_options(options);
+        result?;
+
+        // a model of another version fails every frame, so refuse it on load
+        self.version = SileroVersion::detect(&self.input_names()?)?;
+        log::debug!("Silero vad model version: {:?}", self.version);
+        Ok(())
+    }
+
+    unsafe fn input_names(&self) -> Result<Vec<String>, ProgramError> {
+        let get_allocator: unsafe extern "system" fn(*mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_GET_ALLOCATOR_WITH_DEFAULT_OPTIONS);
+        let allocator_free: unsafe extern "system" fn(*mut c_void, *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_ALLOCATOR_FREE);
+        let get_input_count: unsafe extern "system" fn(*const c_void, *mut usize) -> OrtStatusPtr =
+            self.func(ORT_SESSION_GET_INPUT_COUNT);
+        let get_input_name: unsafe extern "system" fn(*const c_void, usize, *mut c_void, *mut *mut c_char) -> OrtStatusPtr =
+            self.func(ORT_SESSION_GET_INPUT_NAME);
+
+        // the default allocator is owned by onnxruntime and never released
+        let mut allocator: *mut c_void = std::ptr::null_mut();
+        self.check(get_allocator(&mut allocator))?;
+        let mut count = 0usize;
+        self.check(get_input_count(self.session, &mut count))?;
+        let mut names = vec![];
+        for index in 0..count {
+            let mut name: *mut c_char = std::ptr::null_mut();
+            self.check(get_input_name(self.session, index, allocator, &mut name))?;
+            names.push(CStr::from_ptr(name).to_string_lossy().to_string());
+            self.check(allocator_free(allocator, name as *mut c_void))?;
+        }
+        Ok(names)
+    }
+
+    unsafe fn create_tensor<T>(&self, data: &mut [T], shape: &[i64], data_type: c_int) -> Result<*mut c_void, ProgramError> {
+        let create_tensor: unsafe extern "system" fn(*const c_void, *mut c_void, usize, *const i64, usize, c_int, *mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_CREATE_TENSOR_WITH_DATA_AS_ORT_VALUE);
+        let mut value: *mut c_void = std::ptr::null_mut();
+        self.check(create_tensor(self.memory_info,
+                                 data.as_mut_ptr() as *mut c_void,
+                                 std::mem::size_of_val(data),
+                                 shape.as_ptr(),
+                                 shape.len(),
+                                 data_type,
+                                 &mut value))?;
+        Ok(value)
+    }
+
+    unsafe fn release_values(&self, values: &[*mut c_void]) {
+        let release_value: unsafe extern "system" fn(*mut c_void) = self.func(ORT_RELEASE_VALUE);
+        for value in values {
+            if !value.is_null() {
+                release_value(*value);
+            }
+        }
+    }
+
+    /// speech probability of one frame, lstm state and context are updated for the next frame
+    fn frame_probability(&self,
+                         frame: &[f32; SILERO_FRAME_SIZE],
+                         state: &mut SileroState) -> Result<f32, ProgramError> {
+        let mut inputs: Vec<*mut c_void> = vec![];
+        let mut outputs: Vec<*mut c_void> = vec![std::ptr::null_mut(); self.version.output_names().len()];
+        let result = unsafe {
+            self.run_frame(frame, state, &mut inputs, &mut outputs)
+        };
+        unsafe {
+            self.release_values(&inputs);
+            self.release_values(&outputs);
+        }
+        result
+    }
+
+    unsafe fn run_frame(&self,
+                        frame: &[f32; SILERO_FRAME_SIZE],
+                        state: &mut SileroState,
+                        inputs: &mut Vec<*mut c_void>,
+                        outputs: &mut [*mut c_void]) -> Result<f32, ProgramError> {
+        // tensors only borrow these buffers, they must outlive the run
+        let mut sr = [SILERO_SAMPLE_RATE as i64];
+        let mut samples = [0f32; CONTEXT_SIZE + SILERO_FRAME_SIZE];
+        let mut lstm = state.lstm;
+        let input_names: &[&str] = match self.version {
+            SileroVersion::V4 => {
+                samples[..SILERO_FRAME_SIZE].copy_from_slice(frame);
+                let (h, c) = lstm.split_at_mut(STATE_SIZE / 2);
+                inputs.push(self.create_tensor(&mut samples[..SILERO_FRAME_SIZE], &[1, SILERO_FRAME_SIZE as i64],
+                                               ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT)?);
+                // sample rate is a scalar
+                inputs.push(self.create_tensor(&mut sr, &[], ONNX_TENSOR_ELEMENT_DATA_TYPE_INT64)?);
+                inputs.push(self.create_tensor(h, &[2, 1, 64], ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT)?);
+                inputs.push(self.create_tensor(c, &[2, 1, 64], ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT)?);
+                &["input", "sr", "h", "c"]
+            }
+            SileroVersion::V5 => {
+                samples[..CONTEXT_SIZE].copy_from_slice(&state.context);
+                samples[CONTEXT_SIZE..].copy_from_slice(frame);
+                inputs.push(self.create_tensor(&mut samples, &[1, (CONTEXT_SIZE + SILERO_FRAME_SIZE) as i64],
+                                               ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT)?);
+                inputs.push(self.create_tensor(&mut lstm, &[2, 1, 128], ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT)?);
+                inputs.push(self.create_tensor(&mut sr, &[], ONNX_TENSOR_ELEMENT_DATA_TYPE_INT64)?);
+                &["input", "state", "sr"]
+            }
+        };
+
+        let input_names: Vec<CString> = input_names.iter().map(|name| CString::new(*name).unwrap()).collect();
+        let output_names: Vec<CString> = self.version.output_names().iter()
+            .map(|name| CString::new(*name).unwrap())
+            .collect();
+        let input_name_ptrs: Vec<*const c_char> = input_names.iter().map(|name| name.as_ptr()).collect();
+        let output_name_ptrs: Vec<*const c_char> = output_names.iter().map(|name| name.as_ptr()).collect();
+
+        let run: unsafe extern "system" fn(*mut c_void, *const c_void,
+                                           *const *const c_char, *const *const c_void, usize,
+                                           *const *const c_char, usize, *mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_RUN);
+        self.check(run(self.session,
+                       std::ptr::null(),
+                       input_name_ptrs.as_ptr(),
+                       inputs.as_ptr() as *const *const c_void,
+                       inputs.len(),
+                       output_name_ptrs.as_ptr(),
+                       outputs.len(),
+                       outputs.as_mut_ptr()))?;
+
+        let get_tensor_mutable_data: unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> OrtStatusPtr =
+            self.func(ORT_GET_TENSOR_MUTABLE_DATA);
+        let mut data: *mut c_void = std::ptr::null_mut();
+        self.check(get_tensor_mutable_data(outputs[0], &mut data))?;
+        let probability = *(data as *const f32);
+        // v4 returns `hn` and `cn`, v5 returns both halves as one `stateN`
+        let size = STATE_SIZE / (outputs.len() - 1);
+        let mut offset = 0;
+        for output in &outputs[1..] {
+            self.check(get_tensor_mutable_data(*output, &mut data))?;
+            state.lstm[offset..offset + size].copy_from_slice(std::slice::from_raw_parts(data as *const f32, size));
+            offset += size;
+        }
+        state.context.copy_from_slice(&frame[SILERO_FRAME_SIZE - CONTEXT_SIZE..]);
+        Ok(probability)
+    }
+
+    /// speech probability of every frame of mono 16k samples
+    fn probabilities(&self, samples: &[f32], state: &mut SileroState) -> Result<Vec<f32>, ProgramError> {
+        let mut frame = [0f32; SILERO_FRAME_SIZE];
+        let mut probabilities = vec![];
+        for chunk in samples.chunks_exact(SILERO_FRAME_SIZE) {
+            frame.copy_from_slice(chunk);
+            probabilities.push(self.frame_probability(&frame, state)?);
+        }
+        Ok(probabilities)
+    }
+}
+
+impl Drop for SileroModel {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.session.is_null() {
+                let release_session: unsafe extern "system" fn(*mut c_void) = self.func(ORT_RELEASE_SESSION);
+                release_session(self.session);
+            }
+            if !self.memory_info.is_null() {
+                let release_memory_info: unsafe extern "system" fn(*mut c_void) = self.func(ORT_RELEASE_MEMORY_INFO);
+                release_memory_info(self.memory_info);
+            }
+            if !self.env.is_null() {
+                let release_env: unsafe extern "system" fn(*mut c_void) = self.func(ORT_RELEASE_ENV);
+                release_env(self.env);
+            }
+        }
+    }
+}
+
+/// speech probability of every 512 samples of mono 16k audio, remaining samples are ignored,
+/// model of given paths is loaded on first use, model and onnxruntime library in `vad` directory
+/// are used if paths are not set
+pub fn speech_probabilities(model_path: Option<&str>,
+                            library_path: Option<&str>,
+                            samples: &[f32],
+                            state: &mut SileroState) -> Result<Vec<f32>, ProgramError> {
+    let paths = (library_file(library_path), model_file(model_path));
+    let lock = SILERO_MODEL.clone();
+    let mut holder = lock.lock().unwrap();
+    if holder.paths.as_ref() != Some(&paths) {
+        holder.model = None;
+        holder.error = None;
+        match SileroModel::load(&paths.0, &paths.1) {
+            Ok(model) => {
+                log::debug!("Load silero vad model {} success", paths.1.to_string_lossy());
+                holder.model = Some(model);
+            }
+            Err(err) => {
+                log::error!("Failed to load silero vad model {} by onnxruntime {}, err: {}",
+                    paths.1.to_string_lossy(), paths.0.to_string_lossy(), err);
+                holder.error = Some(err.to_string());
+            }
+        }
+        holder.paths = Some(paths);
+    }
+    match &holder.model {
+        Some(model) => model.probabilities(samples, state),
+        None => Err(ProgramError::from(holder.error.clone()
+            .unwrap_or_else(|| "Silero vad model is not loaded".to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect_version() {
+        assert_eq!(SileroVersion::detect(&names(&["input", "sr", "h", "c"])).unwrap(), SileroVersion::V4);
+        assert_eq!(SileroVersion::detect(&names(&["input", "state", "sr"])).unwrap(), SileroVersion::V5);
+        let err = SileroVersion::detect(&names(&["x"])).unwrap_err();
+        assert!(err.to_string().contains("[x]"));
+    }
+
+    #[test]
+    #[ignore = "needs silero vad model and onnxruntime library in vad directory"]
+    fn test_speech_probabilities_of_bundled_model() {
+        let mut state = SileroState::default();
+        let silence = vec![0f32; SILERO_FRAME_SIZE * 4 + 100];
+        let probabilities = speech_probabilities(None, None, &silence, &mut state).unwrap();
+        assert_eq!(probabilities.len(), 4);
+        assert!(probabilities.iter().all(|probability| *probability < 0.5));
+    }
+}
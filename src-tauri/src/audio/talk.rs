@@ -1,6 +1,6 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use cpal::traits::DeviceTrait;
 use lazy_static::lazy_static;
@@ -9,11 +9,21 @@ use tokio::sync::broadcast;
 use tokio::sync::broadcast::{Receiver, Sender};
 
 use crate::audio::listener;
+use crate::audio::listener::{Listener, UtteranceSegmenter};
+use crate::audio::vad;
+use crate::audio::vad::{EnergyVad, VoiceActivityDetector};
 use crate::common::{app, constants};
 use crate::config::voice_recognition;
+use crate::config::voice_recognition::{TalkParams, VadConfig};
 use crate::controller::{audio_manager, generator};
 use crate::controller::errors::ProgramError;
 use crate::controller::recognizer;
+use crate::controller::recognizer::SpeechRecognizer;
+
+// audio kept after the key is released, keys are often released right at the end of the last word
+const PUSH_TO_TALK_TAIL_MS: u64 = 200;
+// interval of checking whether the push-to-talk key is still held
+const KEY_POLL_MS: u64 = 20;
 
 lazy_static! {
     static ref TALKING: AtomicBool = AtomicBool::new(false);
@@ -26,25 +36,183 @@ enum TalkRecordingState {
     DetectSpeech,
 }
 
-pub struct TalkParams {
-    pub vad_thold: f32,
-    pub freq_thold: f32,
-    pub voice_ms: u32,
-    pub verbose: bool,
+/// strip non-speech annotations whisper may output and keep the first line only
+fn clean_text(text: &str) -> String {
+    let mut text_heard = String::from(text.trim());
+    // remove text between brackets []
+    {
+        let re = Regex::new(r"\[.*?\]").unwrap();
+        text_heard = re.replace_all(&*text_heard, "").to_string();
+    }
+    // remove text between brackets ()
+    {
+        let re = Regex::new(r"\(.*?\)").unwrap();
+        text_heard = re.replace_all(&*text_heard, "").to_string();
+    }
+
+    // take first line
+    {
+        let re = Regex::new(r"\n").unwrap();
+        let index = re.find(&text_heard).map(|m| m.start()).unwrap_or_else(|| text_heard.len());
+        text_heard.truncate(index);
+    }
+
+    // remove leading and trailing whitespace
+    {
+        let re = Regex::new(r"^\s+|\s+$").unwrap();
+        text_heard = re.replace_all(&text_heard, "").to_string();
+    }
+    text_heard
 }
 
-impl TalkParams {
-    pub fn default() -> Self {
-        TalkParams {
-            vad_thold: 0.6,
-            freq_thold: 100.0,
-            voice_ms: 10000,
-            verbose: false,
+/// emit recognized text and generate audio of it if configured
+async fn on_text_heard(text_heard: String) {
+    log::info!("Recognized: {}", text_heard.clone());
+
+    // emit  events
+    app::silent_emit_all(constants::event::ON_AUDIO_RECOGNIZE_TEXT,
+                         text_heard.clone());
+    app::silent_emit_all(constants::event::ON_RECORDING_RECOGNIZE_TEXT,
+                         text_heard.clone());
+
+    let config = {
+        let manager =
+            voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
+        manager.get_config()
+    };
+    if config.generate_after {
+        // generate audio by text
+        let index = generator::generate_audio(text_heard, !config.skip_translation()).await;
+        if let Some(cache) = index {
+            // play generated audio
+            match generator::PLAY_AUDIO_CHANNEL.send(cache.name.clone()).await {
+                Ok(_) => {
+                    log::debug!("Send generated audio to play channel success");
+                }
+                Err(err) => {
+                    log::error!("Failed to send generated audio to play channel, err: {}", err);
+                }
+            }
         }
     }
 }
 
-async fn start_talk_process(params: &TalkParams) -> Result<(), ProgramError> {
+/// talk params of current config, read on every loop so changes apply without restarting
+async fn get_talk_params() -> TalkParams {
+    let manager =
+        voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
+    manager.get_config().talk
+}
+
+/// recognizer of current config if streaming mode is enabled and supported
+async fn get_streaming_recognizer() -> Option<Box<dyn SpeechRecognizer>> {
+    let config = {
+        let manager =
+            voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
+        manager.get_config()
+    };
+    if !config.streaming {
+        return None;
+    }
+    match recognizer::get_recognizer(&config) {
+        Ok(recognizer) => recognizer.filter(|recognizer| recognizer.supports_streaming()),
+        Err(err) => {
+            log::error!("Unable to get streaming recognizer, err: {}", err);
+            None
+        }
+    }
+}
+
+/// one step of sliding window streaming mode like whisper.cpp stream example:
+/// audio since current line started is recognized every step and shown as partial text,
+/// the line is finished when speech ends or the line reaches max voice length
+/// and its text is returned
+async fn streaming_step(audio: &mut Listener,
+                        recognizer: &dyn SpeechRecognizer,
+                        line_ms: &mut u32,
+                        params: &TalkParams,
+                        channels: u16,
+                        sample_rate: u32) -> Result<Option<String>, ProgramError> {
+    tokio::time::sleep(Duration::from_millis(params.step_ms as u64)).await;
+    *line_ms = (*line_ms + params.step_ms).min(params.voice_ms);
+
+    let mut pcmf32_line = Vec::<f32>::new();
+    audio.get(*line_ms, &mut pcmf32_line);
+    let text_heard = clean_text(&recognizer::recognize_by(recognizer, &pcmf32_line, channels, sample_rate).await?);
+    if !text_heard.is_empty() {
+        app::silent_emit_all(constants::event::ON_RECORDING_STATE,
+                             TalkRecordingState::DetectSpeech);
+        app::silent_emit_all(constants::event::ON_RECORDING_RECOGNIZE_TEXT,
+                             text_heard.clone());
+    }
+
+    let mut pcmf32_probe = Vec::<f32>::new();
+    audio.get(params.probe_ms, &mut pcmf32_probe);
+    let speech_end = vad::speech_ended(&mut pcmf32_probe, channels, sample_rate, params);
+    let line_full = *line_ms >= params.voice_ms;
+    if (speech_end && !text_heard.is_empty()) || line_full {
+        *line_ms = 0;
+        audio.clear();
+        return Ok(Some(text_heard));
+    }
+    Ok(None)
+}
+
+/// utterances of talk process split by configured vad, audio is fed since the position tracker created
+struct UtteranceTracker {
+    vad_config: VadConfig,
+    vad: Box<dyn VoiceActivityDetector>,
+    segmenter: UtteranceSegmenter,
+    // absolute position of audio to be fed next
+    position: u64,
+}
+
+impl UtteranceTracker {
+    fn new(vad_config: &VadConfig, position: u64, channels: u16, sample_rate: u32) -> Self {
+        UtteranceTracker {
+            vad_config: vad_config.clone(),
+            vad: vad::get_vad(vad_config),
+            segmenter: UtteranceSegmenter::new(position, channels, sample_rate, vad::FRAME_MS),
+            position,
+        }
+    }
+
+    /// feed audio since last poll to vad, returns samples of utterances ended
+    fn poll(&mut self, audio: &Listener, params: &TalkParams, channels: u16, sample_rate: u32) -> Vec<Vec<f32>> {
+        let end = audio.position();
+        let mut samples = vec![];
+        let start = audio.get_range(self.position, end, &mut samples);
+        if start != self.position {
+            // audio is overwritten or cleared before fed, frames would be misaligned
+            log::debug!("Audio from {} to {} is lost, track utterances again", self.position, start);
+            self.vad = vad::get_vad(&self.vad_config);
+            self.segmenter = UtteranceSegmenter::new(start, channels, sample_rate, vad::FRAME_MS);
+        }
+        self.position = end;
+
+        let flags = match self.vad.speech_frames(&samples, channels, sample_rate, params) {
+            Ok(flags) => flags,
+            Err(err) => {
+                // keep vad config so that the failed vad is not created again until config changes
+                log::error!("Vad {} failed, fall back to energy vad, err: {}", self.vad.name(), err);
+                self.vad = Box::new(EnergyVad::default());
+                self.vad.speech_frames(&samples, channels, sample_rate, params).unwrap_or_default()
+            }
+        };
+
+        let mut utterances = vec![];
+        for speech in flags {
+            if let Some((from, to)) = self.segmenter.push(speech, params) {
+                let mut utterance = vec![];
+                audio.get_range(from, to, &mut utterance);
+                utterances.push(utterance);
+            }
+        }
+        utterances
+    }
+}
+
+async fn start_talk_process() -> Result<(), ProgramError> {
     let device = audio_manager::get_input_device().await?;
     let config = device.default_input_config()?;
     let sample_rate = config.sample_rate().0;
@@ -58,17 +226,12 @@ async fn start_talk_process(params: &TalkParams) -> Result<(), ProgramError> {
         return Ok(());
     }
 
-    let vad_thold = params.vad_thold;
-    let freq_thold = params.freq_thold;
-    let verbose = params.verbose;
-    let voice_ms = params.voice_ms;
-
     tauri::async_runtime::spawn(async move {
         let (interrupted_tx, _) = &*TALKING_STOP_SIG;
         let mut interrupted_rx = interrupted_tx.subscribe();
 
-        let mut force_speak = false;
-        let mut pcmf32_cur = Vec::<f32>::new();
+        let mut tracker: Option<UtteranceTracker> = None;
+        let mut line_ms = 0;
         let mut is_running = true;
         loop {
             if !is_running {
@@ -79,98 +242,54 @@ async fn start_talk_process(params: &TalkParams) -> Result<(), ProgramError> {
                 response = async {
                     app::silent_emit_all(constants::event::ON_RECORDING_STATE,
                                          TalkRecordingState::Recording);
-                    sleep(Duration::from_millis(100));
-                    audio.get(2000, &mut pcmf32_cur);
-
-                    let vad_ok = listener::vad_simple(&mut pcmf32_cur, sample_rate,
-                        1250, vad_thold, freq_thold, verbose);
-
-                    // check vad
-                    if vad_ok || force_speak {
-                        app::silent_emit_all(constants::event::ON_RECORDING_STATE,
-                                             TalkRecordingState::DetectSpeech);
 
-                        log::debug!("Speech detected, ready to recognize");
-
-                        audio.get(voice_ms, &mut pcmf32_cur);
-
-                        let mut text_heard = String::new();
-                        if !force_speak {
-                            let reg_res = recognizer::recognize(
-                                &pcmf32_cur, channels, sample_rate).await;
-                            match reg_res {
-                                Ok(text) => {
-                                    text_heard = String::from(text.trim());
-                                }
-                                Err(err) => {
-                                    log::error!("Failed to recognize audio, err: {}", err);
-                                    audio.clear();
-                                    return Ok(());
+                    let params = get_talk_params().await;
+                    listener::set_denoise(params.denoise);
+                    listener::set_agc(params.agc);
+                    if let Some(recognizer) = get_streaming_recognizer().await {
+                        // streaming mode clears audio buffer, utterances are tracked again from scratch
+                        tracker = None;
+                        let step = streaming_step(&mut audio, &*recognizer, &mut line_ms,
+                                                  &params, channels, sample_rate).await;
+                        match step {
+                            Ok(Some(text_heard)) => {
+                                if !text_heard.is_empty() {
+                                    on_text_heard(text_heard).await;
                                 }
                             }
+                            Ok(None) => {}
+                            Err(err) => {
+                                log::error!("Failed to recognize audio, err: {}", err);
+                                line_ms = 0;
+                                audio.clear();
+                            }
                         }
+                        return Ok(());
+                    }
+                    line_ms = 0;
 
-                        // remove text between brackets []
-                        {
-                            let re = Regex::new(r"\[.*?\]").unwrap();
-                            text_heard = re.replace_all(&*text_heard, "").to_string();
-                        }
-                        // remove text between brackets ()
-                        {
-                            let re = Regex::new(r"\(.*?\)").unwrap();
-                            text_heard = re.replace_all(&*text_heard, "").to_string();
-                        }
-
-                        // take first line
-                        {
-                            let re = Regex::new(r"\n").unwrap();
-                            let index = re.find(&text_heard).map(|m| m.start()).unwrap_or_else(|| text_heard.len());
-                            text_heard.truncate(index);
-                        }
-
-                        // remove leading and trailing whitespace
-                        {
-                            let re = Regex::new(r"^\s+|\s+$").unwrap();
-                            text_heard = re.replace_all(&text_heard, "").to_string();
-                        }
-
-                        if text_heard.is_empty() || force_speak {
-                            audio.clear();
-                            return Ok(());
-                        }
-
-                        force_speak = false;
+                    sleep(Duration::from_millis(100));
+                    let tracker = match tracker.take() {
+                        Some(current) if current.vad_config == params.vad => tracker.insert(current),
+                        _ => tracker.insert(UtteranceTracker::new(&params.vad, audio.position(), channels, sample_rate))
+                    };
 
-                        log::info!("Recognized: {}", text_heard.clone());
+                    for utterance in tracker.poll(&audio, &params, channels, sample_rate) {
+                        app::silent_emit_all(constants::event::ON_RECORDING_STATE,
+                                             TalkRecordingState::DetectSpeech);
 
-                        // emit  events
-                        app::silent_emit_all(constants::event::ON_AUDIO_RECOGNIZE_TEXT,
-                                             text_heard.clone());
-                        app::silent_emit_all(constants::event::ON_RECORDING_RECOGNIZE_TEXT,
-                                             text_heard.clone());
+                        log::debug!("Utterance of {} samples detected, ready to recognize", utterance.len());
 
-                        let gen_audio = {
-                            let manager =
-                                voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
-                            manager.get_config().generate_after
-                        };
-                        if gen_audio {
-                            // generate audio by text
-                            let index = generator::generate_audio(text_heard).await;
-                            if let Some(cache) = index {
-                                // play generated audio
-                                match generator::PLAY_AUDIO_CHANNEL.send(cache.name.clone()).await {
-                                    Ok(_) => {
-                                        log::debug!("Send generated audio to play channel success");
-                                    }
-                                    Err(err) => {
-                                        log::error!("Failed to send generated audio to play channel, err: {}", err);
-                                    }
-                                }
+                        let text_heard = match recognizer::recognize(&utterance, channels, sample_rate).await {
+                            Ok(text) => clean_text(&text),
+                            Err(err) => {
+                                log::error!("Failed to recognize audio, err: {}", err);
+                                continue;
                             }
+                        };
+                        if !text_heard.is_empty() {
+                            on_text_heard(text_heard).await;
                         }
-
-                        audio.clear();
                     }
                     Ok::<_, ProgramError>(())
                 } => {
@@ -202,14 +321,72 @@ async fn start_talk_process(params: &TalkParams) -> Result<(), ProgramError> {
     Ok(())
 }
 
-pub async fn start(params: &TalkParams) -> Result<(), ProgramError> {
+async fn push_to_talk_process(key_held: impl Fn() -> bool) -> Result<(), ProgramError> {
+    let device = audio_manager::get_input_device().await?;
+    let config = device.default_input_config()?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels();
+
+    let params = get_talk_params().await;
+    listener::set_denoise(params.denoise);
+    listener::set_agc(params.agc);
+    let samples = {
+        let mut audio = listener::LISTENER.lock().await;
+        audio.init(device)?;
+        audio.resume()?;
+        let start = audio.position();
+        app::silent_emit_all(constants::event::ON_RECORDING_STATE,
+                             TalkRecordingState::Recording);
+
+        // recording longer than audio buffer would overwrite its beginning
+        let started = Instant::now();
+        let max_duration = Duration::from_millis(audio.len_ms() as u64);
+        while key_held() && started.elapsed() < max_duration {
+            tokio::time::sleep(Duration::from_millis(KEY_POLL_MS)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(PUSH_TO_TALK_TAIL_MS)).await;
+
+        let mut samples = vec![];
+        audio.get_range(start, audio.position(), &mut samples);
+        audio.pause()?;
+        samples
+    };
+
+    let min_samples = params.min_speech_ms as usize * sample_rate as usize / 1000 * channels as usize;
+    if samples.len() < min_samples {
+        log::debug!("Push-to-talk recording of {} samples is too short, ignored", samples.len());
+        return Ok(());
+    }
+
+    app::silent_emit_all(constants::event::ON_RECORDING_STATE,
+                         TalkRecordingState::DetectSpeech);
+    let text_heard = clean_text(&recognizer::recognize(&samples, channels, sample_rate).await?);
+    if !text_heard.is_empty() {
+        on_text_heard(text_heard).await;
+    }
+    Ok(())
+}
+
+/// record while `key_held` returns true and recognize the whole recording once the key is released,
+/// does nothing if talk process is running
+pub async fn push_to_talk(key_held: impl Fn() -> bool) -> Result<(), ProgramError> {
+    if TALKING.swap(true, Ordering::AcqRel) {
+        log::debug!("Talk process is running, push-to-talk ignored");
+        return Ok(());
+    }
+    let result = push_to_talk_process(key_held).await;
+    TALKING.store(false, Ordering::Release);
+    result
+}
+
+pub async fn start() -> Result<(), ProgramError> {
     if TALKING.load(Ordering::Acquire) {
         return Err(ProgramError::from("Talk process already running"));
     }
 
     TALKING.store(true, Ordering::Release);
 
-    let process = start_talk_process(params).await;
+    let process = start_talk_process().await;
     match process {
         Ok(_) => {
             log::debug!("Talk process started");
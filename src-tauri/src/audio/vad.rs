@@ -0,0 +1,248 @@
+use crate::audio::{listener, silero};
+use crate::config::voice_recognition::{TalkParams, VadBySilero, VadConfig};
+use crate::controller::errors::ProgramError;
+use crate::controller::recognizer;
+
+/// length of frames flagged by [VoiceActivityDetector::speech_frames], same as a silero frame
+pub const FRAME_MS: u32 = 32;
+
+// silero marks the end of speech with a lower threshold than its start to avoid flickering
+const SILERO_NEG_THOLD_OFFSET: f32 = 0.15;
+// noise floor tracked by energy vad rises this ratio per frame, about 4x in 10 seconds
+const NOISE_FLOOR_RISE: f32 = 1.0045;
+// noise floor of digital silence, about -80dB
+const MIN_NOISE_FLOOR: f32 = 1e-4;
+
+/// detects whether speech just ended in the latest audio
+pub trait VoiceActivityDetector: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `samples` are the latest `probe_ms` of interleaved audio, speech ends when
+    /// there is speech before the last `tail_ms` and none within it, used by streaming mode
+    fn speech_ended(&self,
+                    samples: &mut Vec<f32>,
+                    channels: u16,
+                    sample_rate: u32,
+                    params: &TalkParams) -> Result<bool, ProgramError>;
+
+    /// speech flag of every [FRAME_MS] of interleaved audio, `samples` continue samples of the
+    /// previous call and samples not filling a frame are kept for the next call
+    fn speech_frames(&mut self,
+                     samples: &[f32],
+                     channels: u16,
+                     sample_rate: u32,
+                     params: &TalkParams) -> Result<Vec<bool>, ProgramError>;
+}
+
+fn to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples.chunks(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// detect speech by energy over the noise floor, or by energy of the tail against the whole
+/// probe window in streaming mode, cheap but triggered by loud noises like keyboard clicks
+/// and easy to miss quiet speakers
+#[derive(Default)]
+pub struct EnergyVad {
+    // mono samples not filling a frame yet
+    pending: Vec<f32>,
+    // frames flagged, frame boundaries are calculated from it so that frames do not drift
+    frames: u64,
+    noise_floor: Option<f32>,
+}
+
+impl EnergyVad {
+    /// frame is speech when its energy exceeds the noise floor by `1 / vad_thold`, the floor follows
+    /// quieter frames at once and rises slowly so that it adapts to louder background noise
+    fn is_speech(&mut self, frame: &mut Vec<f32>, sample_rate: u32, params: &TalkParams) -> bool {
+        if params.freq_thold > 0.0 {
+            listener::high_pass_filter(frame, params.freq_thold, sample_rate as f32);
+        }
+        let energy = frame.iter().map(|x| x.abs()).sum::<f32>() / frame.len().max(1) as f32;
+        let noise_floor = match self.noise_floor {
+            Some(floor) => (floor * NOISE_FLOOR_RISE).min(energy),
+            None => energy
+        };
+        self.noise_floor = Some(noise_floor);
+        if params.verbose {
+            log::info!("energy_vad: energy: {}, noise_floor: {}, vad_thold: {}", energy, noise_floor, params.vad_thold);
+        }
+        energy * params.vad_thold > noise_floor.max(MIN_NOISE_FLOOR)
+    }
+}
+
+impl VoiceActivityDetector for EnergyVad {
+    fn name(&self) -> &'static str {
+        "Energy"
+    }
+
+    fn speech_ended(&self,
+                    samples: &mut Vec<f32>,
+                    _channels: u16,
+                    sample_rate: u32,
+                    params: &TalkParams) -> Result<bool, ProgramError> {
+        Ok(listener::vad_simple(samples, sample_rate, params.tail_ms,
+                                params.vad_thold, params.freq_thold, params.verbose))
+    }
+
+    fn speech_frames(&mut self,
+                     samples: &[f32],
+                     channels: u16,
+                     sample_rate: u32,
+                     params: &TalkParams) -> Result<Vec<bool>, ProgramError> {
+        self.pending.extend(to_mono(samples, channels));
+        let frame_end = |frame: u64| (frame * FRAME_MS as u64 * sample_rate as u64 / 1000) as usize;
+        let mut flags = vec![];
+        loop {
+            let frame_len = frame_end(self.frames + 1) - frame_end(self.frames);
+            if frame_len == 0 || self.pending.len() < frame_len {
+                break;
+            }
+            let mut frame: Vec<f32> = self.pending.drain(..frame_len).collect();
+            flags.push(self.is_speech(&mut frame, sample_rate, params));
+            self.frames += 1;
+        }
+        Ok(flags)
+    }
+}
+
+/// detect speech by silero vad model run by onnxruntime on cpu
+pub struct SileroVad {
+    config: VadBySilero,
+    state: silero::SileroState,
+    // mono 16k samples not filling a frame yet
+    pending: Vec<f32>,
+    // whether last frame is speech, speech continues until probability drops below the lower threshold
+    triggered: bool,
+}
+
+impl SileroVad {
+    pub fn new(config: VadBySilero) -> Self {
+        SileroVad {
+            config,
+            state: silero::SileroState::default(),
+            pending: vec![],
+            triggered: false,
+        }
+    }
+
+    fn neg_threshold(&self) -> f32 {
+        (self.config.threshold - SILERO_NEG_THOLD_OFFSET).max(0.0)
+    }
+}
+
+impl VoiceActivityDetector for SileroVad {
+    fn name(&self) -> &'static str {
+        "Silero"
+    }
+
+    fn speech_ended(&self,
+                    samples: &mut Vec<f32>,
+                    channels: u16,
+                    sample_rate: u32,
+                    params: &TalkParams) -> Result<bool, ProgramError> {
+        let samples = recognizer::prepare_samples(samples, channels, sample_rate, silero::SILERO_SAMPLE_RATE)?;
+        let probabilities = silero::speech_probabilities(self.config.model_path.as_deref(),
+                                                         self.config.library_path.as_deref(),
+                                                         &samples,
+                                                         &mut silero::SileroState::default())?;
+        let tail_frames = (params.tail_ms / FRAME_MS) as usize;
+        if params.verbose {
+            log::info!("silero_vad: probabilities: {:?}, tail_frames: {}", probabilities, tail_frames);
+        }
+        Ok(speech_ended_by_probabilities(&probabilities, tail_frames, self.config.threshold, self.neg_threshold()))
+    }
+
+    fn speech_frames(&mut self,
+                     samples: &[f32],
+                     channels: u16,
+                     sample_rate: u32,
+                     params: &TalkParams) -> Result<Vec<bool>, ProgramError> {
+        let samples = recognizer::prepare_samples(&samples.to_vec(), channels, sample_rate, silero::SILERO_SAMPLE_RATE)?;
+        self.pending.extend(samples);
+        let frames = self.pending.len() / silero::SILERO_FRAME_SIZE;
+        let probabilities = silero::speech_probabilities(self.config.model_path.as_deref(),
+                                                         self.config.library_path.as_deref(),
+                                                         &self.pending[..frames * silero::SILERO_FRAME_SIZE],
+                                                         &mut self.state)?;
+        self.pending.drain(..frames * silero::SILERO_FRAME_SIZE);
+        if params.verbose {
+            log::info!("silero_vad: probabilities: {:?}", probabilities);
+        }
+        let neg_threshold = self.neg_threshold();
+        Ok(probabilities.into_iter()
+            .map(|p| {
+                self.triggered = if self.triggered { p >= neg_threshold } else { p >= self.config.threshold };
+                self.triggered
+            })
+            .collect())
+    }
+}
+
+/// speech ends if any frame before the tail is speech and no frame of the tail is
+fn speech_ended_by_probabilities(probabilities: &[f32], tail_frames: usize, threshold: f32, neg_threshold: f32) -> bool {
+    if tail_frames == 0 || probabilities.len() <= tail_frames {
+        return false;
+    }
+    let (head, tail) = probabilities.split_at(probabilities.len() - tail_frames);
+    head.iter().any(|p| *p >= threshold) && tail.iter().all(|p| *p < neg_threshold)
+}
+
+pub fn get_vad(config: &VadConfig) -> Box<dyn VoiceActivityDetector> {
+    match config {
+        VadConfig::Energy => Box::new(EnergyVad::default()),
+        VadConfig::Silero(silero_config) => Box::new(SileroVad::new(silero_config.clone())),
+    }
+}
+
+/// detect end of speech by configured vad, energy vad is used instead if it fails,
+/// e.g. silero model or onnxruntime library is missing
+pub fn speech_ended(samples: &mut Vec<f32>,
+                    channels: u16,
+                    sample_rate: u32,
+                    params: &TalkParams) -> bool {
+    let vad = get_vad(&params.vad);
+    match vad.speech_ended(samples, channels, sample_rate, params) {
+        Ok(ended) => ended,
+        Err(err) => {
+            log::debug!("Vad {} failed, fall back to energy vad, err: {}", vad.name(), err);
+            listener::vad_simple(samples, sample_rate, params.tail_ms,
+                                 params.vad_thold, params.freq_thold, params.verbose)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speech_ended_by_probabilities() {
+        // speech followed by silence
+        assert!(speech_ended_by_probabilities(&[0.1, 0.9, 0.8, 0.1, 0.05], 2, 0.5, 0.35));
+        // still speaking at the tail
+        assert!(!speech_ended_by_probabilities(&[0.1, 0.9, 0.8, 0.4, 0.05], 2, 0.5, 0.35));
+        // no speech at all, e.g. a keyboard click silero scores low
+        assert!(!speech_ended_by_probabilities(&[0.1, 0.3, 0.2, 0.1, 0.05], 2, 0.5, 0.35));
+        // not enough frames
+        assert!(!speech_ended_by_probabilities(&[0.9, 0.1], 2, 0.5, 0.35));
+    }
+
+    #[test]
+    fn test_energy_speech_frames() {
+        let params = TalkParams { freq_thold: 0.0, ..TalkParams::default() };
+        let mut vad = EnergyVad::default();
+        // 1000Hz stereo, a frame is 32 samples per channel
+        let mut samples = vec![0.01f32; 32 * 2 * 3];
+        samples.extend(vec![0.5f32; 32 * 2 * 2]);
+        samples.extend(vec![0.01f32; 32 * 2 + 10]);
+        let flags = vad.speech_frames(&samples, 2, 1000, &params).unwrap();
+        assert_eq!(flags, vec![false, false, false, true, true, false]);
+        // remaining samples are kept for the next call
+        assert_eq!(vad.pending.len(), 5);
+    }
+}
@@ -60,7 +60,7 @@ pub async fn play_audio(index: String) -> Option<bool> {
 #[tauri::command]
 pub async fn generate_audio(text: String) -> Option<AudioCacheIndex> {
     log::info!("Call cmd generate audio by text: {}", text.clone());
-    let index = generator::generate_audio(text).await;
+    let index = generator::generate_audio(text, true).await;
     if let Some(cache) = index {
         // play generated silently
         generator::play_audio_silently(cache.name.clone());
@@ -1,11 +1,13 @@
 use crate::common::{app, constants};
-use crate::config::{auto_translation, voice_engine, voice_recognition};
+use crate::config::{auto_translation, glossary, text_processing, voice_engine, voice_recognition};
 use crate::config::auto_translation::AutoTranslationConfig;
+use crate::config::glossary::GlossaryConfig;
+use crate::config::text_processing::TextProcessingConfig;
 use crate::config::voice_engine::VoiceEngineConfig;
-use crate::config::voice_recognition::VoiceRecognitionConfig;
+use crate::config::voice_recognition::{TalkParams, VoiceRecognitionConfig};
 use crate::controller::{audio_manager, audio_recorder};
 use crate::controller::audio_manager::{AudioConfigResponseData, AudioSelection, StreamConfig};
-use crate::controller::voice_recognition::whisper;
+use crate::controller::voice_recognition::{vosk, whisper};
 
 #[tauri::command]
 pub async fn get_voice_engine_config() -> Option<VoiceEngineConfig> {
@@ -14,11 +16,18 @@ pub async fn get_voice_engine_config() -> Option<VoiceEngineConfig> {
     Some(manager.get_config())
 }
 
+/// save voice engine config, fields missing in `config` keep their current values
 #[tauri::command]
-pub async fn save_voice_engine_config(config: VoiceEngineConfig) -> Option<bool> {
+pub async fn save_voice_engine_config(config: serde_json::Value) -> Option<bool> {
     let mut manager = voice_engine::VOICE_ENGINE_CONFIG_MANAGER
         .lock().await;
-    Some(manager.save_config(config))
+    match voice_engine::merge_config(&manager.get_config(), config) {
+        Ok(config) => Some(manager.save_config(config)),
+        Err(err) => {
+            log::error!("Invalid voice engine config, err: {}", err);
+            Some(false)
+        }
+    }
 }
 
 #[tauri::command]
@@ -35,6 +44,34 @@ pub async fn save_auto_translation_config(config: AutoTranslationConfig) -> Opti
     Some(manager.save_config(config))
 }
 
+#[tauri::command]
+pub async fn get_translation_glossary() -> Option<GlossaryConfig> {
+    let manager =
+        glossary::GLOSSARY_CONFIG_MANAGER.read().await;
+    Some(manager.get_config())
+}
+
+#[tauri::command]
+pub async fn save_translation_glossary(config: GlossaryConfig) -> Option<bool> {
+    let mut manager =
+        glossary::GLOSSARY_CONFIG_MANAGER.write().await;
+    Some(manager.save_config(config))
+}
+
+#[tauri::command]
+pub async fn get_text_processing_config() -> Option<TextProcessingConfig> {
+    let manager =
+        text_processing::TEXT_PROCESSING_CONFIG_MANAGER.read().await;
+    Some(manager.get_config())
+}
+
+#[tauri::command]
+pub async fn save_text_processing_config(config: TextProcessingConfig) -> Option<bool> {
+    let mut manager =
+        text_processing::TEXT_PROCESSING_CONFIG_MANAGER.write().await;
+    Some(manager.save_config(config))
+}
+
 #[tauri::command]
 pub async fn get_voice_recognition_config() -> Option<VoiceRecognitionConfig> {
     let manager =
@@ -42,8 +79,9 @@ pub async fn get_voice_recognition_config() -> Option<VoiceRecognitionConfig> {
     Some(manager.get_config())
 }
 
+/// save voice recognition config, fields missing in `config` keep their current values
 #[tauri::command]
-pub async fn save_voice_recognition_config(config: VoiceRecognitionConfig) -> bool {
+pub async fn save_voice_recognition_config(config: serde_json::Value) -> bool {
     // try to get original config to extract record_key
     let old_config =
         voice_recognition::load_voice_recognition_config();
@@ -53,6 +91,13 @@ pub async fn save_voice_recognition_config(config: VoiceRecognitionConfig) -> bo
         return false;
     }
     let old_config = old_config.unwrap();
+    let config = match voice_recognition::merge_config(&old_config, config) {
+        Ok(config) => config,
+        Err(err) => {
+            log::error!("Invalid voice recognition config, err: {}", err);
+            return false;
+        }
+    };
 
     let mut manager = voice_recognition::VOICE_REC_CONFIG_MANAGER
         .write()
@@ -68,6 +113,7 @@ pub async fn save_voice_recognition_config(config: VoiceRecognitionConfig) -> bo
             if let Some(model) = updated.take() {
                 app::silent_emit_all(constants::event::ON_WHISPER_MODEL_LOADED, model);
             }
+            vosk::update_model(&old_config_clone, &config_clone).await;
         });
     }
 
@@ -78,10 +124,37 @@ pub async fn save_voice_recognition_config(config: VoiceRecognitionConfig) -> bo
             log::error!("Failed to update shortcut, err: {}", err)
         }
     }
+    match audio_recorder::update_record_mode(&old_config, &config) {
+        Ok(_) => {}
+        Err(err) => {
+            log::error!("Failed to update record mode, err: {}", err)
+        }
+    }
 
     success
 }
 
+#[tauri::command]
+pub async fn get_talk_params() -> Option<TalkParams> {
+    let manager =
+        voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
+    Some(manager.get_config().talk)
+}
+
+/// save talk params only, a running talk process picks them up on its next loop
+#[tauri::command]
+pub async fn save_talk_params(params: TalkParams) -> Option<bool> {
+    if let Err(err) = params.validate() {
+        log::error!("Invalid talk params, err: {}", err);
+        return Some(false);
+    }
+    let mut manager =
+        voice_recognition::VOICE_REC_CONFIG_MANAGER.write().await;
+    let mut config = manager.get_config();
+    config.talk = params;
+    Some(manager.save_config(config))
+}
+
 #[tauri::command]
 pub async fn get_audio_config() -> Option<AudioConfigResponseData> {
     match audio_manager::get_audio_config().await {
@@ -1,4 +1,6 @@
 pub mod configs;
 pub mod voicevox;
 pub mod whisper;
+pub mod vosk;
 pub mod audios;
+pub mod transcriber;
@@ -0,0 +1,15 @@
+use crate::controller::transcriber;
+use crate::controller::transcriber::TranscriptFormat;
+
+#[tauri::command]
+pub async fn transcribe_file(file: String,
+                             formats: Vec<TranscriptFormat>,
+                             output_dir: Option<String>) -> Option<Vec<String>> {
+    match transcriber::transcribe_file(file.clone(), formats, output_dir).await {
+        Ok(outputs) => Some(outputs),
+        Err(err) => {
+            log::error!("Failed to transcribe file {}, err: {}", file, err);
+            None
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use crate::controller::voice_recognition::vosk;
+
+#[tauri::command]
+pub fn vosk_available_models() -> Option<Vec<String>> {
+    let models = vosk::available_models();
+    match models {
+        Ok(models) => {
+            Some(models)
+        }
+        Err(err) => {
+            log::error!("Failed to list vosk available models, err: {}", err);
+            None
+        }
+    }
+}
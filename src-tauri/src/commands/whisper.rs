@@ -1,4 +1,7 @@
 use crate::controller::voice_recognition::whisper;
+use crate::controller::voice_recognition::whisper_lib::WhisperLibraryStatus;
+use crate::controller::voice_recognition::whisper_models;
+use crate::controller::voice_recognition::whisper_models::WhisperModelInfo;
 
 #[tauri::command]
 pub fn whisper_available_models() -> Option<Vec<String>> {
@@ -13,3 +16,59 @@ pub fn whisper_available_models() -> Option<Vec<String>> {
         }
     }
 }
+
+#[tauri::command]
+pub async fn whisper_remote_models() -> Option<Vec<WhisperModelInfo>> {
+    let models = whisper_models::remote_models().await;
+    match models {
+        Ok(models) => {
+            Some(models)
+        }
+        Err(err) => {
+            log::error!("Failed to list whisper remote models, err: {}", err);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub fn whisper_installed_models() -> Option<Vec<WhisperModelInfo>> {
+    let models = whisper_models::installed_models();
+    match models {
+        Ok(models) => {
+            Some(models)
+        }
+        Err(err) => {
+            log::error!("Failed to list whisper installed models, err: {}", err);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn whisper_install_model(name: String) -> bool {
+    match whisper_models::download_model(&name).await {
+        Ok(_) => true,
+        Err(err) => {
+            log::error!("Failed to install whisper model {}, err: {}", name, err);
+            false
+        }
+    }
+}
+
+/// none if the model is unverifiable or failed to verify
+#[tauri::command]
+pub async fn whisper_verify_model(name: String) -> Option<bool> {
+    match whisper_models::verify_model(&name).await {
+        Ok(verified) => Some(verified),
+        Err(err) => {
+            log::error!("Failed to verify whisper model {}, err: {}", name, err);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn whisper_library_status() -> WhisperLibraryStatus {
+    whisper::library_status().await
+}
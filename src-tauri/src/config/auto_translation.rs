@@ -8,7 +8,8 @@ use crate::config::config;
 use crate::controller::errors::ProgramError;
 
 static TRANSLATION_CONFIG: &str = "auto_translation";
-static SALT: &str = "1435660288";
+// longer than retries of rate limited requests, which sleep 1s, 2s and 3s in turn
+const DEFAULT_TIMEOUT_MS: u64 = 10000;
 
 lazy_static! {
    pub static ref AUTO_TRANS_CONFIG_MANAGER: Mutex<AutoTranslationConfigManager> = Mutex::new(AutoTranslationConfigManager::init());
@@ -16,10 +17,26 @@ lazy_static! {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type")]
-enum AutoTranslateTool {
+pub enum AutoTranslateTool {
     Baidu(TranslateByBaidu)
 }
 
+impl AutoTranslateTool {
+    /// name of translation service, which is recorded along with translated text
+    pub fn name(&self) -> String {
+        match self {
+            AutoTranslateTool::Baidu(_) => "Baidu".to_string()
+        }
+    }
+
+    /// source and target language codes of translation service
+    pub fn languages(&self) -> (String, String) {
+        match self {
+            AutoTranslateTool::Baidu(config) => (config.from.clone(), config.to.clone())
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranslateByBaidu {
     api_addr: String,
@@ -34,6 +51,15 @@ pub struct TranslateByBaidu {
 pub struct AutoTranslationConfig {
     enable: bool,
     tool: AutoTranslateTool,
+    // translators to try in order if previous one failed or timeout
+    #[serde(default)]
+    fallbacks: Vec<AutoTranslateTool>,
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
 }
 
 impl TranslateByBaidu {
@@ -41,18 +67,27 @@ impl TranslateByBaidu {
         self.api_addr.clone()
     }
 
-    pub fn build_params(self, text: &str) -> HashMap<&'static str, String> {
+    /// copy of current config with given source language
+    pub fn with_from(&self, from: &str) -> Self {
+        let mut config = self.clone();
+        config.from = from.to_string();
+        config
+    }
+
+    /// build request params of baidu translation api,
+    /// salt should be a new random value for every request
+    pub fn build_params(self, text: &str, salt: &str) -> HashMap<&'static str, String> {
         let mut params = HashMap::new();
         params.insert("q", text.to_owned());
         params.insert("from", self.from.to_owned());
         params.insert("to", self.to.to_owned());
 
-        let concat = format!("{}{}{}{}", self.app_id, text, SALT, self.secret);
+        let concat = format!("{}{}{}{}", self.app_id, text, salt, self.secret);
         let sign = md5::compute(concat);
         let sign = format!("{:x}", sign);
 
         params.insert("appid", self.app_id.to_owned());
-        params.insert("salt", SALT.to_owned());
+        params.insert("salt", salt.to_owned());
         params.insert("sign", sign);
 
         params
@@ -60,15 +95,18 @@ impl TranslateByBaidu {
 }
 
 impl AutoTranslationConfig {
-    pub fn translated_by_baidu(self) -> (bool, Option<TranslateByBaidu>) {
+    /// all configured translators ordered by priority, empty if translation is not enabled
+    pub fn translators(&self) -> Vec<AutoTranslateTool> {
         if !self.enable {
-            return (false, None);
-        }
-        return match self.tool {
-            AutoTranslateTool::Baidu(config) => {
-                (true, Some(config))
-            }
+            return vec![];
         }
+        let mut tools = vec![self.tool.clone()];
+        tools.extend(self.fallbacks.iter().cloned());
+        tools
+    }
+
+    pub fn get_timeout_ms(&self) -> u64 {
+        self.timeout_ms
     }
 }
 
@@ -83,6 +121,8 @@ fn gen_default_config() -> Result<AutoTranslationConfig, ProgramError> {
             from: "auto".to_string(),
             to: "jp".to_string(),
         }),
+        fallbacks: vec![],
+        timeout_ms: DEFAULT_TIMEOUT_MS,
     };
     config::save_config(TRANSLATION_CONFIG, &default_config)?;
     Ok(default_config)
@@ -158,6 +198,8 @@ mod tests {
                 from: "abc".to_string(),
                 to: "abc".to_string(),
             }),
+            fallbacks: vec![],
+            timeout_ms: DEFAULT_TIMEOUT_MS,
         };
         let json_value = serde_json::to_string(&config).unwrap();
         let json_parsed = serde_json::from_str::<AutoTranslationConfig>(json_value.as_str()).unwrap();
@@ -168,4 +210,13 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_deserialize_config_without_fallbacks() {
+        let json_value = r#"{"enable":true,"tool":{"type":"Baidu","api_addr":"localhost:8080",
+            "appId":"app_1","secret":"secret","from":"auto","to":"jp"}}"#;
+        let json_parsed = serde_json::from_str::<AutoTranslationConfig>(json_value).unwrap();
+        assert_eq!(json_parsed.translators().len(), 1);
+        assert_eq!(json_parsed.get_timeout_ms(), DEFAULT_TIMEOUT_MS);
+    }
 }
@@ -80,3 +80,29 @@ pub fn load_config<'a, T>(key: &'a str) -> Result<T, ProgramError>
     let result: T = serde_json::from_str(data_decrypted.as_str()).map_err(ProgramError::from)?;
     Ok(result)
 }
+
+/// merge `patch` into `base`, objects are merged by field while other values are replaced,
+/// an object of another `type` replaces the old one as a whole since their fields differ
+pub fn merge_json(base: &mut serde_json::Value, patch: serde_json::Value) {
+    match (base, patch) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(patch)) => {
+            let same_type = match (base.get("type"), patch.get("type")) {
+                (Some(base_type), Some(patch_type)) => base_type == patch_type,
+                _ => true
+            };
+            if !same_type {
+                *base = patch;
+                return;
+            }
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_json(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
@@ -0,0 +1,83 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::config::config;
+use crate::controller::errors::ProgramError;
+
+static GLOSSARY_CONFIG: &str = "translation_glossary";
+
+lazy_static! {
+    pub static ref GLOSSARY_CONFIG_MANAGER: RwLock<GlossaryConfigManager> = RwLock::new(GlossaryConfigManager::init());
+}
+
+/// a source term which should always be translated into the fixed target term
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlossaryTerm {
+    pub(crate) source: String,
+    pub(crate) target: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlossaryConfig {
+    #[serde(default)]
+    pub(crate) terms: Vec<GlossaryTerm>,
+    // terms which should be kept as is, like channel names
+    #[serde(default)]
+    pub(crate) protected: Vec<String>,
+}
+
+fn gen_default_config() -> Result<GlossaryConfig, ProgramError> {
+    let default_config = GlossaryConfig::default();
+    config::save_config(GLOSSARY_CONFIG, &default_config)?;
+    Ok(default_config)
+}
+
+pub fn load_glossary_config() -> Result<GlossaryConfig, ProgramError> {
+    let default_config = config::get_config_raw::<GlossaryConfig>(GLOSSARY_CONFIG)?;
+    if default_config.is_none() {
+        let default_config = gen_default_config()?;
+        return Ok(default_config);
+    }
+    config::load_config::<GlossaryConfig>(GLOSSARY_CONFIG)
+}
+
+pub fn save_glossary_config(config: &GlossaryConfig) -> Result<(), ProgramError> {
+    config::save_config(GLOSSARY_CONFIG, config)
+}
+
+#[derive(Debug)]
+pub struct GlossaryConfigManager {
+    config: GlossaryConfig,
+}
+
+impl GlossaryConfigManager {
+    pub fn init() -> Self {
+        let config = load_glossary_config();
+        match config {
+            Ok(data) => GlossaryConfigManager { config: data },
+            Err(err) => {
+                log::error!("Failed to init glossary config manager, load config with error: {}", err);
+                panic!("Unable to init glossary config manager");
+            }
+        }
+    }
+
+    pub fn get_config(&self) -> GlossaryConfig {
+        self.config.clone()
+    }
+
+    pub fn save_config(&mut self, config: GlossaryConfig) -> bool {
+        let result = save_glossary_config(&config);
+        match result {
+            Ok(_) => {
+                self.config = config;
+                true
+            }
+            Err(err) => {
+                log::error!("Failed to save glossary config, err: {}", err);
+                false
+            }
+        }
+    }
+}
@@ -2,3 +2,5 @@ pub mod config;
 pub mod voice_engine;
 pub mod auto_translation;
 pub mod voice_recognition;
+pub mod glossary;
+pub mod text_processing;
@@ -0,0 +1,130 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::config::config;
+use crate::controller::errors::ProgramError;
+
+static TEXT_PROCESSING_CONFIG: &str = "text_processing";
+
+lazy_static! {
+    pub static ref TEXT_PROCESSING_CONFIG_MANAGER: RwLock<TextProcessingConfigManager> = RwLock::new(TextProcessingConfigManager::init());
+}
+
+/// config of normalizing text into speakable words before synthesis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalizerConfig {
+    pub(crate) enable: bool,
+    pub(crate) strip_markdown: bool,
+    pub(crate) replace_urls: bool,
+    pub(crate) replace_emoji: bool,
+    pub(crate) expand_abbreviations: bool,
+    // read symbols & and @ as words, off by default since they often appear in names and ids
+    #[serde(default)]
+    pub(crate) expand_symbols: bool,
+    // dates, times, currencies, units and plain numbers
+    pub(crate) expand_numbers: bool,
+    // net slang "www" read as laughing
+    #[serde(default = "default_replace_laugh")]
+    pub(crate) replace_laugh: bool,
+    // collapse characters repeated more than this times, 0 means never collapse
+    pub(crate) max_repeat: usize,
+}
+
+fn default_replace_laugh() -> bool {
+    true
+}
+
+impl Default for NormalizerConfig {
+    fn default() -> Self {
+        NormalizerConfig {
+            enable: false,
+            strip_markdown: true,
+            replace_urls: true,
+            replace_emoji: true,
+            expand_abbreviations: true,
+            expand_symbols: false,
+            expand_numbers: true,
+            replace_laugh: true,
+            max_repeat: 3,
+        }
+    }
+}
+
+/// user defined katakana reading of a latin word, takes precedence over the bundled dictionary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KatakanaReading {
+    pub(crate) word: String,
+    pub(crate) reading: String,
+}
+
+/// config of converting latin words into katakana readings for japanese voices
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KatakanaConfig {
+    pub(crate) enable: bool,
+    #[serde(default)]
+    pub(crate) overrides: Vec<KatakanaReading>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TextProcessingConfig {
+    #[serde(default)]
+    pub(crate) normalizer: NormalizerConfig,
+    #[serde(default)]
+    pub(crate) katakana: KatakanaConfig,
+}
+
+fn gen_default_config() -> Result<TextProcessingConfig, ProgramError> {
+    let default_config = TextProcessingConfig::default();
+    config::save_config(TEXT_PROCESSING_CONFIG, &default_config)?;
+    Ok(default_config)
+}
+
+pub fn load_text_processing_config() -> Result<TextProcessingConfig, ProgramError> {
+    let default_config = config::get_config_raw::<TextProcessingConfig>(TEXT_PROCESSING_CONFIG)?;
+    if default_config.is_none() {
+        let default_config = gen_default_config()?;
+        return Ok(default_config);
+    }
+    config::load_config::<TextProcessingConfig>(TEXT_PROCESSING_CONFIG)
+}
+
+pub fn save_text_processing_config(config: &TextProcessingConfig) -> Result<(), ProgramError> {
+    config::save_config(TEXT_PROCESSING_CONFIG, config)
+}
+
+#[derive(Debug)]
+pub struct TextProcessingConfigManager {
+    config: TextProcessingConfig,
+}
+
+impl TextProcessingConfigManager {
+    pub fn init() -> Self {
+        let config = load_text_processing_config();
+        match config {
+            Ok(data) => TextProcessingConfigManager { config: data },
+            Err(err) => {
+                log::error!("Failed to init text processing config manager, load config with error: {}", err);
+                panic!("Unable to init text processing config manager");
+            }
+        }
+    }
+
+    pub fn get_config(&self) -> TextProcessingConfig {
+        self.config.clone()
+    }
+
+    pub fn save_config(&mut self, config: TextProcessingConfig) -> bool {
+        let result = save_text_processing_config(&config);
+        match result {
+            Ok(_) => {
+                self.config = config;
+                true
+            }
+            Err(err) => {
+                log::error!("Failed to save text processing config, err: {}", err);
+                false
+            }
+        }
+    }
+}
@@ -68,11 +68,55 @@ enum EngineConfig {
     VoiceVox(VoiceVoxEngineConfig)
 }
 
+/// loudness normalization of generated audio, so that styles and speakers sound equally loud
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LoudnessConfig {
+    pub(crate) enable: bool,
+    // integrated loudness of normalized audio, streaming platforms mostly use -14 to -16
+    #[serde(default = "default_target_lufs")]
+    pub(crate) target_lufs: f32,
+    // sample peak of normalized audio never exceeds it, gain is reduced instead
+    #[serde(default = "default_peak_db")]
+    pub(crate) peak_db: f32,
+}
+
+fn default_target_lufs() -> f32 {
+    -16.0
+}
+
+fn default_peak_db() -> f32 {
+    -1.0
+}
+
+impl LoudnessConfig {
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if !(-70.0..0.0).contains(&self.target_lufs) {
+            return Err(ProgramError::from(format!("target_lufs {} should be in [-70, 0)", self.target_lufs)));
+        }
+        if self.peak_db > 0.0 {
+            return Err(ProgramError::from(format!("peak_db {} should not be above 0", self.peak_db)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for LoudnessConfig {
+    fn default() -> Self {
+        LoudnessConfig {
+            enable: false,
+            target_lufs: default_target_lufs(),
+            peak_db: default_peak_db(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct VoiceEngineConfig {
     #[serde(rename = "type")]
     engine_type: EngineType,
     config: EngineConfig,
+    #[serde(default)]
+    pub(crate) loudness: LoudnessConfig,
 }
 
 impl VoiceEngineConfig {
@@ -89,6 +133,16 @@ impl VoiceEngineConfig {
     }
 }
 
+/// config with fields of `patch` replacing those of `current`, fields missing in `patch` are kept,
+/// e.g. loudness is kept when the engine page saves its engine fields only
+pub fn merge_config(current: &VoiceEngineConfig, patch: serde_json::Value) -> Result<VoiceEngineConfig, ProgramError> {
+    let mut merged = serde_json::to_value(current)?;
+    config::merge_json(&mut merged, patch);
+    let config: VoiceEngineConfig = serde_json::from_value(merged)?;
+    config.loudness.validate()?;
+    Ok(config)
+}
+
 fn gen_default_config() -> Result<VoiceEngineConfig, ProgramError> {
     let default_config = VoiceEngineConfig {
         engine_type: EngineType::VoiceVox,
@@ -101,6 +155,7 @@ fn gen_default_config() -> Result<VoiceEngineConfig, ProgramError> {
             speaker_uuid: "7ffcb7ce-00ec-4bdc-82cd-45a8889e43ff".to_string(),
             speaker_style_id: 0,
         }),
+        loudness: LoudnessConfig::default(),
     };
     config::save_config(VOICE_ENGINE_CONFIG, &default_config)?;
     Ok(default_config)
@@ -222,6 +277,42 @@ impl VoiceEngineConfigManager {
 mod tests {
     use super::*;
 
+    fn test_config() -> VoiceEngineConfig {
+        VoiceEngineConfig {
+            engine_type: EngineType::VoiceVox,
+            config: EngineConfig::VoiceVox(VoiceVoxEngineConfig {
+                config_type: VoiceVoxConfigType::Http,
+                protocol: "http".to_string(),
+                api_addr: "localhost:8080".to_string(),
+                device: "cpu".to_string(),
+                cpu_arch: None,
+                speaker_uuid: "7ffcb7ce-00ec-4bdc-82cd-45a8889e43ff".to_string(),
+                speaker_style_id: 0,
+            }),
+            loudness: LoudnessConfig { enable: true, target_lufs: -14.0, peak_db: -1.0 },
+        }
+    }
+
+    #[test]
+    fn test_merge_config_keeps_loudness() {
+        // engine page saves engine fields only
+        let config = merge_config(&test_config(), serde_json::json!({
+            "type": "VoiceVox",
+            "config": {"type": "VoiceVox", "config": {"api_addr": "localhost:50021"}}
+        })).unwrap();
+        assert!(config.loudness.enable);
+        assert_eq!(config.loudness.target_lufs, -14.0);
+        assert_eq!(config.get_voice_vox_config().unwrap().api_addr, "localhost:50021");
+        assert_eq!(config.get_voice_vox_config().unwrap().speaker_style_id, 0);
+
+        let config = merge_config(&config, serde_json::json!({"loudness": {"enable": false}})).unwrap();
+        assert!(!config.loudness.enable);
+        assert_eq!(config.loudness.target_lufs, -14.0);
+
+        assert!(merge_config(&config, serde_json::json!({"loudness": {"target_lufs": 3.0}})).is_err());
+        assert!(merge_config(&config, serde_json::json!({"loudness": {"peak_db": 1.0}})).is_err());
+    }
+
     #[test]
     fn test_serialize_config() {
         let protocol = "http".to_string();
@@ -237,6 +328,7 @@ mod tests {
                 speaker_uuid: "7ffcb7ce-00ec-4bdc-82cd-45a8889e43ff".to_string(),
                 speaker_style_id: 0,
             }),
+            loudness: LoudnessConfig::default(),
         };
         let json_value = serde_json::to_string(&config).unwrap();
         let json_parsed = serde_json::from_str::<VoiceEngineConfig>(json_value.as_str()).unwrap();
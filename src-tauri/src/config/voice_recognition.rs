@@ -2,6 +2,7 @@ use tokio::sync::RwLock;
 
 use lazy_static::lazy_static;
 
+use crate::audio::listener;
 use crate::config::config;
 use crate::controller::errors::ProgramError;
 
@@ -19,6 +20,61 @@ pub enum WhisperConfigType {
     Binary,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, strum_macros::EnumString, serde::Serialize, serde::Deserialize)]
+pub enum WhisperSamplingStrategy {
+    #[strum(serialize = "greedy")]
+    Greedy,
+    #[strum(serialize = "beam_search")]
+    BeamSearch,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, strum_macros::EnumString, serde::Serialize, serde::Deserialize)]
+pub enum WhisperTask {
+    // recognize speech as text of the spoken language
+    #[default]
+    #[strum(serialize = "transcribe")]
+    Transcribe,
+    // recognize speech of any language directly as english text
+    #[strum(serialize = "translate")]
+    Translate,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, strum_macros::EnumString, serde::Serialize, serde::Deserialize)]
+pub enum RecordMode {
+    // record key starts and stops talk process, speech is split by vad
+    #[default]
+    #[strum(serialize = "toggle")]
+    Toggle,
+    // record while record key is held and recognize on release
+    #[strum(serialize = "push_to_talk")]
+    PushToTalk,
+    // talk process starts at launch, record key still pauses and resumes it
+    #[strum(serialize = "always_on")]
+    AlwaysOn,
+}
+
+/// decoding params of whisper.cpp library, params not set fall back to the built-in values
+/// which are tuned for speed, set them to trade speed for accuracy
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct WhisperDecodeParams {
+    pub(crate) strategy: Option<WhisperSamplingStrategy>,
+    // candidates of greedy sampling when temperature > 0
+    pub(crate) best_of: Option<i32>,
+    pub(crate) beam_size: Option<i32>,
+    pub(crate) patience: Option<f32>,
+    pub(crate) temperature: Option<f32>,
+    // temperature increment of fallback decoding when thresholds are not met, 0 disables fallback
+    pub(crate) temperature_inc: Option<f32>,
+    pub(crate) n_threads: Option<i32>,
+    // should be multiple of 64, 1500 means full 30s audio context
+    pub(crate) audio_ctx: Option<i32>,
+    pub(crate) no_speech_thold: Option<f32>,
+    pub(crate) logprob_thold: Option<f32>,
+    pub(crate) suppress_blank: Option<bool>,
+    pub(crate) single_segment: Option<bool>,
+    pub(crate) no_context: Option<bool>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RecognizeByWhisper {
     pub(crate) config_type: WhisperConfigType,
@@ -26,12 +82,246 @@ pub struct RecognizeByWhisper {
     pub(crate) use_model: String,
     pub(crate) api_addr: String,
     pub(crate) language: Option<String>,
+    // only used by binary config type
+    #[serde(default)]
+    pub(crate) decode: WhisperDecodeParams,
+    // text to condition the decoder on, like a previous sentence in the expected style
+    #[serde(default)]
+    pub(crate) initial_prompt: Option<String>,
+    // domain terms and speaker names whisper should prefer spelling as is
+    #[serde(default)]
+    pub(crate) vocabulary: Vec<String>,
+    // segments of whisper library whose average token probability is lower than this are dropped
+    #[serde(default = "default_min_confidence")]
+    pub(crate) min_confidence: f32,
+    #[serde(default)]
+    pub(crate) task: WhisperTask,
+    // generate audio of the english text as is without the auto translation, only for translate task
+    #[serde(default)]
+    pub(crate) skip_translation: bool,
+    // whisper.cpp library file or directory of it, library in `whisper` directory is used if not set,
+    // only used by binary config type
+    #[serde(default)]
+    pub(crate) library_path: Option<String>,
+    // max recognitions running concurrently on one loaded model, each takes memory of decoding buffers,
+    // only works with whisper.cpp library of state api
+    #[serde(default = "default_state_pool_size")]
+    pub(crate) state_pool_size: usize,
+}
+
+fn default_min_confidence() -> f32 {
+    0.4
+}
+
+fn default_state_pool_size() -> usize {
+    2
+}
+
+impl RecognizeByWhisper {
+    /// initial prompt with vocabulary terms appended, whisper tends to follow the spellings
+    /// that appear in the prompt
+    pub fn prompt(&self) -> Option<String> {
+        let mut parts: Vec<String> = vec![];
+        if let Some(initial_prompt) = &self.initial_prompt {
+            if !initial_prompt.trim().is_empty() {
+                parts.push(initial_prompt.trim().to_string());
+            }
+        }
+        let terms: Vec<&str> = self.vocabulary.iter()
+            .map(|term| term.trim())
+            .filter(|term| !term.is_empty())
+            .collect();
+        if !terms.is_empty() {
+            parts.push(terms.join(", "));
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, strum_macros::EnumString, serde::Serialize, serde::Deserialize)]
+pub enum TranscriptionResponseFormat {
+    #[strum(serialize = "json")]
+    #[serde(rename = "json")]
+    Json,
+    #[strum(serialize = "verbose_json")]
+    #[serde(rename = "verbose_json")]
+    VerboseJson,
+    #[strum(serialize = "text")]
+    #[serde(rename = "text")]
+    Text,
+}
+
+fn default_response_format() -> TranscriptionResponseFormat {
+    TranscriptionResponseFormat::Json
+}
+
+/// recognize by any openai compatible `/v1/audio/transcriptions` endpoint,
+/// like openai itself, faster-whisper-server, whisper.cpp server or LocalAI
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecognizeByOpenAi {
+    // base address without `/v1`, e.g. http://127.0.0.1:8000
+    pub(crate) api_addr: String,
+    #[serde(default)]
+    pub(crate) api_key: Option<String>,
+    pub(crate) model: String,
+    #[serde(default)]
+    pub(crate) language: Option<String>,
+    #[serde(default)]
+    pub(crate) prompt: Option<String>,
+    #[serde(default)]
+    pub(crate) temperature: f32,
+    #[serde(default = "default_response_format")]
+    pub(crate) response_format: TranscriptionResponseFormat,
+}
+
+/// recognize by vosk library offline, light enough for low-end machines
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecognizeByVosk {
+    // model directory name inside `vosk/models`, or an absolute path of model directory
+    pub(crate) model: String,
+    // file of vosk library or the directory containing it, library in `vosk` directory if not set
+    #[serde(default)]
+    pub(crate) library_path: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum RecognitionTool {
-    Whisper(RecognizeByWhisper)
+    Whisper(RecognizeByWhisper),
+    OpenAi(RecognizeByOpenAi),
+    Vosk(RecognizeByVosk),
+}
+
+/// voice activity detection params of talk process, changes apply to a running talk process;
+/// utterances are segmented by vad frames with pre-roll and hangover, except in streaming mode
+/// which checks the latest probe window for the end of speech instead
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TalkParams {
+    // threshold of energy vad, a frame is speech when its energy exceeds the noise floor by 1 / vad_thold;
+    // in streaming mode, speech ends when the tail is quieter than vad_thold of the whole probe window
+    pub(crate) vad_thold: f32,
+    // cutoff frequency of high pass filter applied before detection
+    pub(crate) freq_thold: f32,
+    // max length of an utterance recognized at once, longer speech is split,
+    // audio buffer of listener keeps 30000ms at most
+    pub(crate) voice_ms: u32,
+    // interval of recognizing partial text in streaming mode
+    pub(crate) step_ms: u32,
+    // length of latest audio checked for the end of speech, streaming mode only
+    #[serde(default = "default_probe_ms")]
+    pub(crate) probe_ms: u32,
+    // length of the tail of probe window which should be silent when speech ends, should be less than
+    // probe_ms, streaming mode only
+    #[serde(default = "default_tail_ms")]
+    pub(crate) tail_ms: u32,
+    // audio kept before the first speech frame of an utterance so that its onset is not clipped
+    #[serde(default = "default_pre_roll_ms")]
+    pub(crate) pre_roll_ms: u32,
+    // silence after speech before the utterance ends, pauses shorter than it do not split utterances
+    #[serde(default = "default_hangover_ms")]
+    pub(crate) hangover_ms: u32,
+    // utterances with less speech are dropped as noise
+    #[serde(default = "default_min_speech_ms")]
+    pub(crate) min_speech_ms: u32,
+    // suppress background noise like fans and keyboards before vad and recognition
+    #[serde(default)]
+    pub(crate) denoise: bool,
+    // level quiet or loud microphones to similar volume before vad and recognition
+    #[serde(default)]
+    pub(crate) agc: bool,
+    pub(crate) verbose: bool,
+    // detector deciding whether speech ended, energy detector uses vad_thold and freq_thold
+    #[serde(default)]
+    pub(crate) vad: VadConfig,
+}
+
+impl TalkParams {
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        // zero step never advances the talk loop, zero window never holds speech
+        for (name, value) in [("step_ms", self.step_ms), ("probe_ms", self.probe_ms), ("voice_ms", self.voice_ms)] {
+            if value == 0 {
+                return Err(ProgramError::from(format!("{} should be greater than 0", name)));
+            }
+        }
+        if self.tail_ms >= self.probe_ms {
+            return Err(ProgramError::from(format!("tail_ms {} should be less than probe_ms {}",
+                                                  self.tail_ms, self.probe_ms)));
+        }
+        if self.voice_ms > listener::BUFFER_MS {
+            return Err(ProgramError::from(format!("voice_ms {} should not exceed audio buffer of {}ms",
+                                                  self.voice_ms, listener::BUFFER_MS)));
+        }
+        Ok(())
+    }
+}
+
+fn default_probe_ms() -> u32 {
+    2000
+}
+
+fn default_tail_ms() -> u32 {
+    1250
+}
+
+fn default_pre_roll_ms() -> u32 {
+    300
+}
+
+fn default_hangover_ms() -> u32 {
+    800
+}
+
+fn default_min_speech_ms() -> u32 {
+    250
+}
+
+impl Default for TalkParams {
+    fn default() -> Self {
+        TalkParams {
+            vad_thold: 0.6,
+            freq_thold: 100.0,
+            voice_ms: 10000,
+            step_ms: 2000,
+            probe_ms: default_probe_ms(),
+            tail_ms: default_tail_ms(),
+            pre_roll_ms: default_pre_roll_ms(),
+            hangover_ms: default_hangover_ms(),
+            min_speech_ms: default_min_speech_ms(),
+            denoise: false,
+            agc: false,
+            verbose: false,
+            vad: VadConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum VadConfig {
+    #[default]
+    Energy,
+    Silero(VadBySilero),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VadBySilero {
+    // onnx model file, `vad/silero_vad.onnx` if not set
+    #[serde(default)]
+    pub(crate) model_path: Option<String>,
+    // onnxruntime library file or its directory, library in `vad` directory if not set
+    #[serde(default)]
+    pub(crate) library_path: Option<String>,
+    // speech probability of a frame to be regarded as speech
+    #[serde(default = "default_silero_threshold")]
+    pub(crate) threshold: f32,
+}
+
+fn default_silero_threshold() -> f32 {
+    0.5
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -40,10 +330,41 @@ pub struct VoiceRecognitionConfig {
     pub(crate) generate_after: bool,
     #[serde(rename = "recordKey")]
     pub(crate) record_key: String,
+    #[serde(default)]
+    pub(crate) record_mode: RecordMode,
     pub(crate) tool: RecognitionTool,
+    // recognize while speaking and show partial text, only works with recognizers support streaming
+    #[serde(default)]
+    pub(crate) streaming: bool,
+    #[serde(default)]
+    pub(crate) talk: TalkParams,
 }
 
 impl VoiceRecognitionConfig {
+    /// whether recognized text is already translated by recognizer and needs no auto translation
+    pub fn skip_translation(&self) -> bool {
+        match &self.tool {
+            RecognitionTool::Whisper(config) => {
+                config.task == WhisperTask::Translate && config.skip_translation
+            }
+            _ => false
+        }
+    }
+
+    /// language configured for the recognizer, none means detected by the recognizer
+    pub fn language(&self) -> Option<String> {
+        match &self.tool {
+            RecognitionTool::Whisper(config) => config.language.clone(),
+            RecognitionTool::OpenAi(config) => config.language.clone(),
+            RecognitionTool::Vosk(_) => None,
+        }
+    }
+
+    /// whether talk process should keep running since launch
+    pub fn always_on(&self) -> bool {
+        self.enable && self.record_mode == RecordMode::AlwaysOn
+    }
+
     pub fn recognize_by_whisper(self) -> (bool, Option<RecognizeByWhisper>) {
         if !self.enable {
             return (false, None);
@@ -52,21 +373,43 @@ impl VoiceRecognitionConfig {
             RecognitionTool::Whisper(config) => {
                 (true, Some(config))
             }
+            _ => (true, None)
         };
     }
 }
 
+/// config with fields of `patch` replacing those of `current`, fields missing in `patch` are kept
+/// so that a settings page only showing some of fields won't reset the others
+pub fn merge_config(current: &VoiceRecognitionConfig, patch: serde_json::Value) -> Result<VoiceRecognitionConfig, ProgramError> {
+    let mut merged = serde_json::to_value(current)?;
+    config::merge_json(&mut merged, patch);
+    let config: VoiceRecognitionConfig = serde_json::from_value(merged)?;
+    config.talk.validate()?;
+    Ok(config)
+}
+
 fn gen_default_config() -> Result<VoiceRecognitionConfig, ProgramError> {
     let empty_str = "".to_string();
     let default_config = VoiceRecognitionConfig {
         enable: false,
         generate_after: false,
         record_key: "F1".to_string(),
+        record_mode: RecordMode::default(),
+        streaming: false,
+        talk: TalkParams::default(),
         tool: RecognitionTool::Whisper(RecognizeByWhisper {
             config_type: WhisperConfigType::Http,
             use_model: "base".to_string(),
             api_addr: empty_str.clone(),
             language: None,
+            decode: WhisperDecodeParams::default(),
+            initial_prompt: None,
+            vocabulary: vec![],
+            min_confidence: default_min_confidence(),
+            task: WhisperTask::Transcribe,
+            skip_translation: false,
+            library_path: None,
+            state_pool_size: default_state_pool_size(),
         }),
     };
     config::save_config(RECOGNITION_CONFIG, &default_config)?;
@@ -126,3 +469,51 @@ impl VoiceRecognitionConfigManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> VoiceRecognitionConfig {
+        serde_json::from_value(serde_json::json!({
+            "enable": true,
+            "generate_after": false,
+            "recordKey": "F1",
+            "record_mode": "PushToTalk",
+            "streaming": true,
+            "tool": {"type": "Vosk", "model": "vosk-model-small-ja"},
+            "talk": {"vad_thold": 0.6, "freq_thold": 100.0, "voice_ms": 10000, "step_ms": 2000, "verbose": false},
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_merge_keeps_missing_fields() {
+        let config = merge_config(&test_config(), serde_json::json!({
+            "enable": false,
+            "recordKey": "F2",
+        })).unwrap();
+        assert!(!config.enable);
+        assert_eq!(config.record_key, "F2");
+        assert_eq!(config.record_mode, RecordMode::PushToTalk);
+        assert!(config.streaming);
+        assert!(matches!(config.tool, RecognitionTool::Vosk(_)));
+
+        // a tool of another type is replaced as a whole
+        let config = merge_config(&config, serde_json::json!({
+            "tool": {"type": "Whisper", "config_type": "Http", "use_model": "base", "api_addr": "", "language": null},
+        })).unwrap();
+        assert!(matches!(config.tool, RecognitionTool::Whisper(_)));
+        assert_eq!(config.talk.voice_ms, 10000);
+    }
+
+    #[test]
+    fn test_validate_talk_params() {
+        assert!(TalkParams::default().validate().is_ok());
+        assert!(TalkParams { tail_ms: 2000, probe_ms: 2000, ..TalkParams::default() }.validate().is_err());
+        assert!(TalkParams { voice_ms: listener::BUFFER_MS + 1, ..TalkParams::default() }.validate().is_err());
+        assert!(TalkParams { step_ms: 0, ..TalkParams::default() }.validate().is_err());
+        assert!(TalkParams { probe_ms: 0, tail_ms: 0, ..TalkParams::default() }.validate().is_err());
+        assert!(TalkParams { voice_ms: 0, ..TalkParams::default() }.validate().is_err());
+        assert!(merge_config(&test_config(), serde_json::json!({"talk": {"voice_ms": 60000}})).is_err());
+    }
+}
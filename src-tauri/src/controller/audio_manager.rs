@@ -11,6 +11,8 @@ use tokio::sync::broadcast;
 use tokio::sync::broadcast::{Receiver, Sender};
 use tokio::sync::RwLock as AsyncRwLock;
 
+use crate::audio::denoise::SwitchableDenoiser;
+use crate::audio::loudness::Agc;
 use crate::common::{app, constants};
 use crate::controller::errors::{CommonError, ProgramError};
 
@@ -20,6 +22,8 @@ lazy_static! {
     static ref MIC_STREAM_STOP: (Sender<()>, Receiver<()>) = broadcast::channel(1);
     static ref MIC_STREAM_STOP_ACCEPT: (Sender<()>, Receiver<()>) = broadcast::channel(1);
     static ref MIC_STREAMING: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    static ref MIC_DENOISE: AtomicBool = AtomicBool::new(false);
+    static ref MIC_AGC: AtomicBool = AtomicBool::new(false);
 }
 
 pub struct MicStreamWrapper {
@@ -73,6 +77,12 @@ pub struct AudioSelectionConfig {
 pub struct StreamConfig {
     stream_input: bool,
     stream_mic_input: bool,
+    // suppress background noise of mic input streamed to VB audio cable
+    #[serde(default)]
+    denoise_mic_input: bool,
+    // level volume of mic input streamed to VB audio cable
+    #[serde(default)]
+    agc_mic_input: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +101,8 @@ impl AudioManager {
             stream: StreamConfig {
                 stream_input: false,
                 stream_mic_input: false,
+                denoise_mic_input: false,
+                agc_mic_input: false,
             },
         }
     }
@@ -217,9 +229,20 @@ pub async fn start_mic_streaming() -> Result<(), ProgramError> {
         let sample_rate = input_config.sample_rate().0;
         let channel = input_config.channels();
 
+        let mut denoiser = SwitchableDenoiser::new(channel, sample_rate);
+        let mut agc = Agc::new(channel, sample_rate);
         let input_stream = input_device.build_input_stream(
             &input_config.into(),
             move |data: &[f32], _: &_| {
+                let mut data = denoiser.process(MIC_DENOISE.load(Ordering::Acquire), data.to_vec());
+                if MIC_AGC.load(Ordering::Acquire) {
+                    agc.process(&mut data);
+                } else {
+                    agc.reset();
+                }
+                if data.is_empty() {
+                    return;
+                }
                 let mic_stream_clone = MIC_STREAM.clone();
                 let mic_stream = mic_stream_clone.lock().unwrap();
                 let samples = rodio::buffer::SamplesBuffer::new(channel, sample_rate, data);
@@ -328,6 +351,9 @@ pub async fn change_stream_config(stream: StreamConfig) -> Result<AudioConfigRes
     let is_streaming = {
         let lock = AUDIO_MANGER.clone();
         let mut manager = lock.write().await;
+        // applies to running mic streaming at once
+        MIC_DENOISE.store(stream.denoise_mic_input, Ordering::Release);
+        MIC_AGC.store(stream.agc_mic_input, Ordering::Release);
         manager.stream = stream;
         manager.stream.stream_input && manager.stream.stream_mic_input
     };
@@ -6,11 +6,11 @@ use tauri::{AppHandle, GlobalShortcutManager, Manager, Window, WindowBuilder, Wi
 use tokio::sync::Mutex as AsyncMutex;
 
 use crate::audio::talk;
-use crate::audio::talk::TalkParams;
 use crate::common::app;
 use crate::config::voice_recognition;
-use crate::config::voice_recognition::VoiceRecognitionConfig;
+use crate::config::voice_recognition::{RecordMode, VoiceRecognitionConfig};
 use crate::controller::errors::ProgramError;
+use crate::utils::windows;
 
 lazy_static! {
     static ref RECORDER: Arc<AsyncMutex<AudioRecorder>> = Arc::new(AsyncMutex::new(AudioRecorder::new()));
@@ -35,7 +35,7 @@ impl AudioRecorder {
     pub async fn start(&mut self) -> Result<(), ProgramError> {
         log::debug!("Start recording");
 
-        talk::start(&TalkParams::default()).await?;
+        talk::start().await?;
 
         self.recording.clone().store(true, Ordering::Release);
 
@@ -76,8 +76,68 @@ async fn check_recorder_async(handle: AppHandle<Wry>) -> Result<(), ProgramError
     Ok(())
 }
 
+/// record while record key is held, presses repeated during recording are ignored
+async fn push_to_talk_async(handle: AppHandle<Wry>, record_key: String) -> Result<(), ProgramError> {
+    let key_code = windows::accelerator_key_code(&record_key)
+        .ok_or_else(|| ProgramError::from(format!("Unsupported push-to-talk key {}", record_key)))?;
+    // lock only to mark as recording, so that is_recording is not blocked while the key is held
+    let recording = {
+        let lock = RECORDER.clone();
+        let recorder = match lock.try_lock() {
+            Ok(recorder) => recorder,
+            Err(_) => return Ok(())
+        };
+        if recorder.recording.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return Ok(());
+        }
+        recorder.recording.clone()
+    };
+    // a failure of opening popup leaves recorder idle
+    if let Err(err) = open_recording_popup(&handle).await {
+        recording.store(false, Ordering::Release);
+        return Err(err);
+    }
+
+    let result = talk::push_to_talk(move || windows::is_key_down(key_code)).await;
+
+    recording.store(false, Ordering::Release);
+    close_recording_popup().await?;
+    result
+}
+
+/// start talk process if not recording
+async fn start_recording(handle: &AppHandle<Wry>) -> Result<(), ProgramError> {
+    let lock = RECORDER.clone();
+    let mut recorder = lock.lock().await;
+    if !recorder.recording.load(Ordering::Acquire) {
+        recorder.start().await?;
+        open_recording_popup(handle).await?;
+    }
+    Ok(())
+}
+
+/// stop talk process if recording
+async fn stop_recording() -> Result<(), ProgramError> {
+    let lock = RECORDER.clone();
+    let mut recorder = lock.lock().await;
+    if recorder.recording.load(Ordering::Acquire) {
+        recorder.stop()?;
+        close_recording_popup().await?;
+    }
+    Ok(())
+}
+
 async fn recorder_handler(app: AppHandle<Wry>) {
-    match check_recorder_async(app.app_handle()).await {
+    let config = {
+        let manager =
+            voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
+        manager.get_config()
+    };
+    let result = match config.record_mode {
+        RecordMode::PushToTalk => push_to_talk_async(app.app_handle(), config.record_key).await,
+        RecordMode::Toggle | RecordMode::AlwaysOn => check_recorder_async(app.app_handle()).await
+    };
+    match result {
         Ok(_) => {}
         Err(err) => {
             log::error!("Unable to check recorder, err: {}", err)
@@ -103,6 +163,36 @@ pub fn start_shortcut(app: AppHandle<Wry>) -> Result<(), ProgramError> {
             })?;
         }
     }
+    if config.always_on() {
+        let app_handle = app.app_handle();
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = start_recording(&app_handle).await {
+                log::error!("Failed to start always-on recording, err: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// start talk process when switched to always-on mode, and stop it when leaving always-on mode,
+/// switching to push-to-talk mode or disabling voice recognition
+pub fn update_record_mode(original: &VoiceRecognitionConfig, new_config: &VoiceRecognitionConfig) -> Result<(), ProgramError> {
+    let app = app::get_app_handle()?;
+    if new_config.always_on() {
+        if !original.always_on() {
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = start_recording(&app).await {
+                    log::error!("Failed to start always-on recording, err: {}", err);
+                }
+            });
+        }
+    } else if original.always_on() || !new_config.enable || new_config.record_mode == RecordMode::PushToTalk {
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = stop_recording().await {
+                log::error!("Failed to stop recording, err: {}", err);
+            }
+        });
+    }
     Ok(())
 }
 
@@ -3,6 +3,8 @@ use std::fmt;
 
 use reqwest::StatusCode;
 
+use crate::controller::translators::baidu_translation::BaiduTranslationError;
+
 #[derive(Debug)]
 pub struct CommonError {
     message: String,
@@ -109,3 +111,4 @@ impl_program_error!(libloading::Error);
 impl_program_error!(tokio::sync::TryLockError);
 impl_program_error!(Box<bincode::ErrorKind>);
 impl_program_error!(CommonError);
+impl_program_error!(BaiduTranslationError);
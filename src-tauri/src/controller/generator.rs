@@ -11,10 +11,12 @@ use sled::transaction::ConflictableTransactionError;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
 
+use crate::audio::loudness;
 use crate::common::{app, constants};
 use crate::config::config::DB_MANAGER;
 use crate::config::voice_engine;
-use crate::controller::{audio_manager, translator};
+use crate::config::voice_engine::LoudnessConfig;
+use crate::controller::{audio_manager, text_processing, translator};
 use crate::controller::errors::ProgramError;
 use crate::controller::voice_engine::voicevox;
 
@@ -113,12 +115,48 @@ pub struct AudioCache {
     source: String,
     translated: String,
     audio: Vec<u8>,
+    // keep new fields at the end, so that caches saved before can still be decoded as legacy
+    translated_by: Option<String>,
+}
+
+/// audio cache layout before `translated_by` was added
+#[derive(Debug, serde::Deserialize)]
+struct LegacyAudioCache {
+    name: String,
+    time: String,
+    source: String,
+    translated: String,
+    audio: Vec<u8>,
+}
+
+impl From<LegacyAudioCache> for AudioCache {
+    fn from(value: LegacyAudioCache) -> Self {
+        AudioCache {
+            name: value.name,
+            time: value.time,
+            source: value.source,
+            translated: value.translated,
+            audio: value.audio,
+            translated_by: None,
+        }
+    }
+}
+
+fn decode_audio_cache(encoded: &[u8]) -> Result<AudioCache, ProgramError> {
+    match bincode::deserialize::<AudioCache>(encoded) {
+        Ok(decoded) => Ok(decoded),
+        Err(_) => {
+            let legacy: LegacyAudioCache = bincode::deserialize(encoded)?;
+            Ok(AudioCache::from(legacy))
+        }
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct AudioCacheDetail {
     source: String,
     translated: String,
+    translated_by: Option<String>,
 }
 
 impl From<AudioCache> for AudioCacheDetail {
@@ -126,6 +164,7 @@ impl From<AudioCache> for AudioCacheDetail {
         AudioCacheDetail {
             source: value.source.clone(),
             translated: value.translated.clone(),
+            translated_by: value.translated_by.clone(),
         }
     }
 }
@@ -158,7 +197,7 @@ pub fn get_index_detail(index: String) -> Result<AudioCacheDetail, ProgramError>
         .open_tree(AUDIO_DATA_TREE_DATA)?;
     let cache = data_tree.get(index.clone().into_bytes())?;
     if let Some(encoded) = cache {
-        let decoded: AudioCache = bincode::deserialize(&encoded)?;
+        let decoded = decode_audio_cache(&encoded)?;
         Ok(AudioCacheDetail::from(decoded))
     } else {
         Err(ProgramError::from(format!("No such record of index {}", index.clone())))
@@ -179,7 +218,10 @@ pub fn delete_index(index: String) -> Result<(), ProgramError> {
     Ok(())
 }
 
-fn save_audio(source: String, translated: String, audio: Bytes) -> Result<AudioCacheIndex, ProgramError> {
+fn save_audio(source: String,
+              translated: String,
+              translated_by: Option<String>,
+              audio: Bytes) -> Result<AudioCacheIndex, ProgramError> {
     let index_name = new_index_name();
     log::debug!("Save audio cache with index: {}", index_name);
     let time: DateTime<Utc> = Utc::now();
@@ -193,6 +235,7 @@ fn save_audio(source: String, translated: String, audio: Bytes) -> Result<AudioC
         source,
         translated,
         audio: audio.to_vec(),
+        translated_by,
     };
 
     let index_tree = DB_MANAGER.clone().db
@@ -215,8 +258,23 @@ fn save_audio(source: String, translated: String, audio: Bytes) -> Result<AudioC
     Ok(cache_index)
 }
 
-/// generate audio content and it's temporary wav content, and return current cache name
-pub async fn generate_audio(text: String) -> Option<AudioCacheIndex> {
+/// normalize loudness of generated wav audio if enabled, original audio is kept if it fails
+fn normalize_audio(audio: Bytes, config: &LoudnessConfig) -> Bytes {
+    if !config.enable {
+        return audio;
+    }
+    match loudness::normalize_wav(&audio, config.target_lufs, config.peak_db) {
+        Ok(normalized) => Bytes::from(normalized),
+        Err(err) => {
+            log::error!("Failed to normalize loudness of generated audio, err: {}", err);
+            audio
+        }
+    }
+}
+
+/// generate audio content and it's temporary wav content, and return current cache name;
+/// text is translated by auto translation first if `translate` is true
+pub async fn generate_audio(text: String, translate: bool) -> Option<AudioCacheIndex> {
     let generating = GEN_AUDIO_MUTEX.load(Ordering::Acquire);
     if generating {
         log::info!("Generate audio is busy");
@@ -225,8 +283,21 @@ pub async fn generate_audio(text: String) -> Option<AudioCacheIndex> {
 
     GEN_AUDIO_MUTEX.store(true, Ordering::Release);
 
-    let translated = translator::translate(text.clone()).await;
-    let translated_text: String = translated.or_else(|| Some(text.clone())).unwrap();
+    let translation = if translate {
+        translator::translate(text.clone()).await
+    } else {
+        Ok(None)
+    };
+    let (translated_text, translated_by) = match translation {
+        Ok(Some(translation)) => (translation.text, Some(translation.translator)),
+        Ok(None) => (text.clone(), None),
+        Err(err) => {
+            // speaking untranslated text by the voice of another language makes no sense
+            log::error!("Failed to translate text, skip generating audio, err: {}", err);
+            GEN_AUDIO_MUTEX.store(false, Ordering::Release);
+            return None;
+        }
+    };
 
     let config = {
         let manager = voice_engine::VOICE_ENGINE_CONFIG_MANAGER.lock().await;
@@ -236,14 +307,17 @@ pub async fn generate_audio(text: String) -> Option<AudioCacheIndex> {
     if config.is_voice_vox_config() {
         // unwrap here since if is true, this must be ok
         let voice_vox_config = config.get_voice_vox_config().unwrap();
-        log::info!("Generating audio by voicevox with text: {}", translated_text.clone());
-        let audio_data = voicevox::gen_audio(&voice_vox_config, translated_text.clone()).await;
+        let speak_text = text_processing::process(translated_text.clone()).await;
+        log::info!("Generating audio by voicevox with text: {}", speak_text.clone());
+        let audio_data = voicevox::gen_audio(&voice_vox_config, speak_text).await;
         log::debug!("Generate audio by voicevox success");
+        let audio_data = audio_data.map(|audio| normalize_audio(audio, &config.loudness));
         match audio_data {
             Ok(audio) => {
                 let save = save_audio(
                     text.clone(),
                     translated_text.clone(),
+                    translated_by.clone(),
                     audio);
                 match save {
                     Ok(index) => {
@@ -334,7 +408,7 @@ fn play_to_vb_audio_cable(wav_bytes: Vec<u8>) -> Result<(), ProgramError> {
 }
 
 async fn play_encoded_audio(encoded: &IVec) -> Result<(), ProgramError> {
-    let decoded: AudioCache = bincode::deserialize(&encoded)?;
+    let decoded = decode_audio_cache(&encoded)?;
     let wav_bytes = decoded.audio;
     if audio_manager::is_stream_input_enabled().await {
         let wav_bytes = wav_bytes.clone();
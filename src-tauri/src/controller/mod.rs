@@ -7,3 +7,5 @@ pub mod audio_manager;
 pub mod audio_recorder;
 pub mod voice_recognition;
 pub mod recognizer;
+pub mod transcriber;
+pub mod text_processing;
@@ -1,6 +1,115 @@
+use async_trait::async_trait;
+
 use crate::config::voice_recognition;
+use crate::config::voice_recognition::{RecognitionTool, VoiceRecognitionConfig, WhisperConfigType};
 use crate::controller::errors::ProgramError;
-use crate::controller::voice_recognition::whisper;
+use crate::controller::voice_recognition::openai::OpenAiRecognizer;
+use crate::controller::voice_recognition::vosk::VoskRecognizer;
+use crate::controller::voice_recognition::whisper::{WhisperHttpRecognizer, WhisperLibRecognizer};
+use crate::utils::audio;
+
+/// text recognized from a span of samples, times are relative to the start of samples
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecognizedSegment {
+    pub text: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+/// a speech to text backend, every backend declares the format it accepts,
+/// samples are converted to mono at the declared rate before they are handed over
+#[async_trait]
+pub trait SpeechRecognizer: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// sample rate of the mono samples this recognizer accepts
+    fn sample_rate(&self) -> u32;
+
+    /// whether this recognizer is fast enough to be called repeatedly on partial audio
+    fn supports_streaming(&self) -> bool;
+
+    /// language codes this recognizer accepts, empty means any language
+    fn supported_languages(&self) -> Vec<String>;
+
+    async fn recognize(&self, samples: &Vec<f32>) -> Result<String, ProgramError>;
+
+    /// recognize samples into timed segments, recognizers without timestamps return
+    /// the whole text as one segment spanning all samples
+    async fn recognize_segments(&self, samples: &Vec<f32>) -> Result<Vec<RecognizedSegment>, ProgramError> {
+        let text = self.recognize(samples).await?;
+        Ok(vec![RecognizedSegment {
+            text,
+            start_ms: 0,
+            end_ms: samples.len() as i64 * 1000 / self.sample_rate() as i64,
+        }])
+    }
+}
+
+/// create recognizer of configured tool, returns none if recognition is disabled,
+/// and error if the configured language is not supported by the recognizer
+pub fn get_recognizer(config: &VoiceRecognitionConfig) -> Result<Option<Box<dyn SpeechRecognizer>>, ProgramError> {
+    if !config.enable {
+        return Ok(None);
+    }
+    let recognizer: Box<dyn SpeechRecognizer> = match config.tool.clone() {
+        RecognitionTool::Whisper(whisper_config) => {
+            match whisper_config.config_type {
+                WhisperConfigType::Http => Box::new(WhisperHttpRecognizer::new(whisper_config)),
+                WhisperConfigType::Binary => Box::new(WhisperLibRecognizer::new(whisper_config)),
+            }
+        }
+        RecognitionTool::OpenAi(openai_config) => Box::new(OpenAiRecognizer::new(openai_config)),
+        RecognitionTool::Vosk(vosk_config) => Box::new(VoskRecognizer::new(vosk_config)),
+    };
+    if let Some(language) = config.language() {
+        check_language(&*recognizer, &language)?;
+    }
+    Ok(Some(recognizer))
+}
+
+fn check_language(recognizer: &dyn SpeechRecognizer, language: &str) -> Result<(), ProgramError> {
+    let languages = recognizer.supported_languages();
+    if languages.is_empty() || languages.iter().any(|supported| supported == language) {
+        Ok(())
+    } else {
+        Err(ProgramError::from(format!("Language {} is not supported by {}, supported languages: {}",
+                                       language, recognizer.name(), languages.join(", "))))
+    }
+}
+
+/// convert samples of any channels and rate to mono samples of target rate
+pub fn prepare_samples(data: &Vec<f32>,
+                       channels: u16,
+                       sample_rate: u32,
+                       target_rate: u32) -> Result<Vec<f32>, ProgramError> {
+    if channels == 0 {
+        return Err(ProgramError::from(format!("unsupported input channel value: {}", channels)));
+    }
+    let mono_samples = if channels > 1 {
+        audio::convert_to_mono(data, channels)
+    } else {
+        data.clone()
+    };
+    if sample_rate == target_rate {
+        return Ok(mono_samples);
+    }
+    log::debug!("Convert audio from rate {} to rate {}", sample_rate, target_rate);
+    samplerate::convert(sample_rate,
+                        target_rate,
+                        1,
+                        samplerate::ConverterType::SincBestQuality,
+                        &mono_samples)
+        .map_err(ProgramError::from)
+}
+
+pub async fn recognize_by(recognizer: &dyn SpeechRecognizer,
+                          data: &Vec<f32>,
+                          channels: u16,
+                          sample_rate: u32) -> Result<String, ProgramError> {
+    let samples = prepare_samples(data, channels, sample_rate, recognizer.sample_rate())?;
+    log::debug!("Recognize {} samples by {}", samples.len(), recognizer.name());
+    recognizer.recognize(&samples).await
+}
 
 pub async fn recognize(data: &Vec<f32>,
                        channels: u16,
@@ -10,21 +119,8 @@ pub async fn recognize(data: &Vec<f32>,
             voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
         manager.get_config()
     };
-    let (rec, by_whisper) = config.recognize_by_whisper();
-    if !rec {
-        return Ok("".to_string());
-    }
-    if let Some(whisper_config) = by_whisper {
-        let result = whisper::asr(&whisper_config, data, channels, sample_rate)
-            .await;
-        return match result {
-            Ok(recognized) => {
-                Ok(recognized)
-            }
-            Err(err) => {
-                Err(err)
-            }
-        };
+    match get_recognizer(&config)? {
+        Some(recognizer) => recognize_by(&*recognizer, data, channels, sample_rate).await,
+        None => Ok("".to_string())
     }
-    return Err(ProgramError::from("No available recognizer"));
 }
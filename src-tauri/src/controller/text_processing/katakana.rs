@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use tauri::regex::{Captures, Regex};
+
+use crate::config::text_processing::KatakanaConfig;
+
+const BUNDLED_DICTIONARY: &str = include_str!("english_katakana.tsv");
+
+lazy_static! {
+    static ref DICTIONARY: HashMap<String, String> = parse_dictionary(BUNDLED_DICTIONARY);
+    static ref WORD: Regex = Regex::new(r"[A-Za-z]+(?:'[A-Za-z]+)?").unwrap();
+}
+
+const LETTERS: [&str; 26] = [
+    "エー", "ビー", "シー", "ディー", "イー", "エフ", "ジー", "エイチ", "アイ", "ジェー",
+    "ケー", "エル", "エム", "エヌ", "オー", "ピー", "キュー", "アール", "エス", "ティー",
+    "ユー", "ブイ", "ダブリュー", "エックス", "ワイ", "ゼット",
+];
+
+// english spelling chunks to romaji-like phonetic spelling, longer chunks first
+const SPELLING_RULES: [(&str, &str); 28] = [
+    ("tion", "shon"),
+    ("sion", "jon"),
+    ("ture", "chaー"),
+    ("ough", "oー"),
+    ("igh", "ai"),
+    ("ck", "ッk"),
+    ("ch", "ch"),
+    ("sh", "sh"),
+    ("ph", "f"),
+    ("th", "s"),
+    ("wh", "w"),
+    ("qu", "kw"),
+    ("ee", "iー"),
+    ("ea", "iー"),
+    ("oo", "uー"),
+    ("ou", "au"),
+    ("ai", "ei"),
+    ("ay", "ei"),
+    ("ey", "ei"),
+    ("oa", "oー"),
+    ("oy", "oi"),
+    ("au", "oー"),
+    ("aw", "oー"),
+    ("ew", "yuー"),
+    ("er", "aー"),
+    ("ir", "aー"),
+    ("ur", "aー"),
+    ("ar", "aー"),
+];
+
+fn parse_dictionary(content: &str) -> HashMap<String, String> {
+    content.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(word, reading)| (word.trim().to_lowercase(), reading.trim().to_string()))
+        .collect()
+}
+
+fn is_vowel(ch: char) -> bool {
+    matches!(ch, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+fn is_consonant(ch: char) -> bool {
+    ch.is_ascii_lowercase() && !is_vowel(ch)
+}
+
+/// read acronyms like "HTML" letter by letter
+fn spell_letters(word: &str) -> String {
+    word.chars()
+        .filter(|ch| ch.is_ascii_alphabetic())
+        .map(|ch| LETTERS[(ch.to_ascii_uppercase() as u8 - b'A') as usize])
+        .collect()
+}
+
+/// convert lowercase english word into romaji-like phonetic spelling by common spelling rules,
+/// vowels are kept as a, i, u, e, o and long vowels are marked as ー, geminate consonants as ッ
+fn to_phonetic(word: &str) -> String {
+    let mut chars: Vec<char> = word.chars().filter(|ch| ch.is_ascii_lowercase()).collect();
+    let len = chars.len();
+
+    // c and g before the silent e are soft, e.g. dance, page
+    if len >= 3 && chars[len - 1] == 'e' {
+        match chars[len - 2] {
+            'c' => chars[len - 2] = 's',
+            'g' => chars[len - 2] = 'j',
+            _ => {}
+        }
+    }
+
+    // "magic e": vowel + consonant + e at the end makes the vowel long, e.g. game, time, home;
+    // mark the vowel with upper case and drop the silent e
+    if len >= 3 && chars[len - 1] == 'e' && is_consonant(chars[len - 2]) && chars[len - 2] != 'r'
+        && is_vowel(chars[len - 3]) && (len < 4 || !is_vowel(chars[len - 4])) {
+        chars[len - 3] = chars[len - 3].to_ascii_uppercase();
+        chars.pop();
+    } else if len > 2 && chars[len - 1] == 'e' && is_consonant(chars[len - 2]) {
+        // silent e at the end
+        chars.pop();
+    }
+    let len = chars.len();
+
+    // short word ends with a single short vowel and a plosive sounds geminate, e.g. cat, bed, fish
+    let mut sokuon_at: Option<usize> = None;
+    if (3..=5).contains(&len) {
+        let ending = if chars.ends_with(&['c', 'h']) || chars.ends_with(&['s', 'h']) { 2 } else { 1 };
+        let ending_start = len - ending;
+        let plosive = ending == 2 || matches!(chars[len - 1], 'k' | 't' | 'p' | 'd' | 'g' | 'x');
+        if plosive && ending_start >= 2 && is_vowel(chars[ending_start - 1])
+            && !is_vowel(chars[ending_start - 2]) {
+            sokuon_at = Some(ending_start);
+        }
+    }
+
+    let word: String = chars.iter().collect();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < len {
+        if sokuon_at == Some(i) {
+            result.push('ッ');
+        }
+        let rest = &word[i..];
+        let ch = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if let Some((chunk, phonetic)) = SPELLING_RULES.iter()
+            .find(|(chunk, _)| rest.starts_with(chunk)) {
+            let after = chars.get(i + chunk.len()).cloned();
+            // r-colored vowels only when not followed by a vowel, e.g. server but not very or area
+            let r_colored = chunk.ends_with('r');
+            if !r_colored || after.map(|c| !is_vowel(c) && c != 'y').unwrap_or(true) {
+                result.push_str(phonetic);
+                i += chunk.len();
+                continue;
+            }
+        }
+
+        let phonetic = match ch {
+            'A' => "eー".to_string(),
+            'I' => "ai".to_string(),
+            'O' => "oー".to_string(),
+            'U' => "yuー".to_string(),
+            'E' => "iー".to_string(),
+            'u' => {
+                // u in closed syllable sounds like a, e.g. bus, cup, jump
+                let next2 = chars.get(i + 2).cloned();
+                let closed = next.map(is_consonant).unwrap_or(false)
+                    && next2.map(|c| !is_vowel(c)).unwrap_or(true);
+                if closed { "a" } else { "u" }.to_string()
+            }
+            'y' => {
+                if i == 0 {
+                    "y"
+                } else if i == len - 1 && is_consonant(chars[i - 1])
+                    && !chars[..i].iter().any(|c| is_vowel(c.to_ascii_lowercase())) {
+                    // y is the only vowel of the word, e.g. sky, fly, my
+                    "ai"
+                } else if i == len - 1 {
+                    "iー"
+                } else {
+                    "i"
+                }.to_string()
+            }
+            'c' => {
+                if matches!(next, Some('e') | Some('i') | Some('y')) { "s" } else { "k" }.to_string()
+            }
+            'o' if next == Some('w') => {
+                i += 1;
+                if i == len - 1 { "oー" } else { "au" }.to_string()
+            }
+            'x' => "ッks".to_string(),
+            'q' => "k".to_string(),
+            'v' => "b".to_string(),
+            'l' => "r".to_string(),
+            _ => ch.to_string(),
+        };
+        result.push_str(&phonetic);
+
+        // double consonants, plosives become geminate while others are read once
+        if next == Some(ch) && is_consonant(ch) {
+            if matches!(ch, 'b' | 'c' | 'd' | 'g' | 'k' | 'p' | 't') {
+                result.pop();
+                result.push('ッ');
+                result.push_str(&phonetic);
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    result
+}
+
+fn syllable(onset: &str, vowel: char) -> Option<&'static str> {
+    let index = match vowel {
+        'a' => 0,
+        'i' => 1,
+        'u' => 2,
+        'e' => 3,
+        'o' => 4,
+        _ => return None,
+    };
+    let row: [&str; 5] = match onset {
+        "" => ["ア", "イ", "ウ", "エ", "オ"],
+        "k" => ["カ", "キ", "ク", "ケ", "コ"],
+        "g" => ["ガ", "ギ", "グ", "ゲ", "ゴ"],
+        "s" => ["サ", "シ", "ス", "セ", "ソ"],
+        "z" => ["ザ", "ジ", "ズ", "ゼ", "ゾ"],
+        "t" => ["タ", "ティ", "トゥ", "テ", "ト"],
+        "d" => ["ダ", "ディ", "ドゥ", "デ", "ド"],
+        "n" => ["ナ", "ニ", "ヌ", "ネ", "ノ"],
+        "h" => ["ハ", "ヒ", "フ", "ヘ", "ホ"],
+        "b" => ["バ", "ビ", "ブ", "ベ", "ボ"],
+        "p" => ["パ", "ピ", "プ", "ペ", "ポ"],
+        "m" => ["マ", "ミ", "ム", "メ", "モ"],
+        "y" => ["ヤ", "イ", "ユ", "イェ", "ヨ"],
+        "r" => ["ラ", "リ", "ル", "レ", "ロ"],
+        "w" => ["ワ", "ウィ", "ウ", "ウェ", "ウォ"],
+        "f" => ["ファ", "フィ", "フ", "フェ", "フォ"],
+        "j" => ["ジャ", "ジ", "ジュ", "ジェ", "ジョ"],
+        "ch" => ["チャ", "チ", "チュ", "チェ", "チョ"],
+        "sh" => ["シャ", "シ", "シュ", "シェ", "ショ"],
+        "ts" => ["ツァ", "ツィ", "ツ", "ツェ", "ツォ"],
+        _ => return None,
+    };
+    Some(row[index])
+}
+
+/// reading of a consonant not followed by any vowel
+fn lone_consonant(onset: &str) -> &'static str {
+    match onset {
+        "n" => "ン",
+        "t" => "ト",
+        "d" => "ド",
+        "ch" => "チ",
+        "j" => "ジ",
+        "sh" => "シュ",
+        "ts" => "ツ",
+        "k" => "ク",
+        "g" => "グ",
+        "s" => "ス",
+        "z" => "ズ",
+        "h" => "フ",
+        "b" => "ブ",
+        "p" => "プ",
+        "m" => "ム",
+        "r" => "ル",
+        "f" => "フ",
+        "w" => "ウ",
+        _ => "",
+    }
+}
+
+fn phonetic_to_katakana(phonetic: &str) -> String {
+    let chars: Vec<char> = phonetic.chars().collect();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch == 'ー' {
+            if !result.ends_with('ー') && !result.is_empty() {
+                result.push(ch);
+            }
+            i += 1;
+            continue;
+        }
+        if ch == 'ッ' {
+            // geminate mark only makes sense before a consonant
+            let next = chars.get(i + 1).cloned();
+            if next.map(|c| is_consonant(c) && !matches!(c, 'n' | 'm' | 'r' | 'y' | 'w')).unwrap_or(false)
+                && !result.is_empty() {
+                result.push(ch);
+            }
+            i += 1;
+            continue;
+        }
+        if is_vowel(ch) {
+            result.push_str(syllable("", ch).unwrap());
+            i += 1;
+            continue;
+        }
+        if !ch.is_ascii_lowercase() {
+            result.push(ch);
+            i += 1;
+            continue;
+        }
+
+        let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let onset = if matches!(pair.as_str(), "ch" | "sh" | "ts") { pair } else { ch.to_string() };
+        i += onset.len();
+
+        let next = chars.get(i).cloned();
+        let next2 = chars.get(i + 1).cloned();
+        // palatalized syllables like kyu
+        if next == Some('y') && matches!(next2, Some('a') | Some('u') | Some('o')) && onset != "y" {
+            if let Some(base) = syllable(&onset, 'i') {
+                result.push_str(base);
+                result.push(match next2 {
+                    Some('a') => 'ャ',
+                    Some('u') => 'ュ',
+                    _ => 'ョ',
+                });
+                i += 2;
+                continue;
+            }
+        }
+        match next.filter(|c| is_vowel(*c)).and_then(|vowel| syllable(&onset, vowel)) {
+            Some(kana) => {
+                result.push_str(kana);
+                i += 1;
+            }
+            None => {
+                // m before b or p is read as n, e.g. jump, number
+                if onset == "m" && matches!(next, Some('b') | Some('p')) {
+                    result.push('ン');
+                } else {
+                    result.push_str(lone_consonant(&onset));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// reading of a single english word, user overrides first, then the bundled dictionary,
+/// acronyms are spelled letter by letter and other words are converted by spelling rules
+pub fn word_to_katakana(word: &str, overrides: &HashMap<String, String>) -> String {
+    let lowercase = word.to_lowercase();
+    if let Some(reading) = overrides.get(&lowercase) {
+        return reading.clone();
+    }
+    if let Some(reading) = DICTIONARY.get(&lowercase) {
+        return reading.clone();
+    }
+    let is_acronym = word.len() <= 5 && word.chars().all(|ch| ch.is_ascii_uppercase());
+    if is_acronym || word.len() == 1 {
+        return spell_letters(word);
+    }
+    phonetic_to_katakana(&to_phonetic(&lowercase.replace('\'', "")))
+}
+
+/// convert all latin alphabet words in text into katakana readings
+pub fn convert(text: &str, config: &KatakanaConfig) -> String {
+    let overrides: HashMap<String, String> = config.overrides.iter()
+        .map(|reading| (reading.word.to_lowercase(), reading.reading.clone()))
+        .collect();
+    WORD.replace_all(text, |caps: &Captures| {
+        word_to_katakana(&caps[0], &overrides)
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::text_processing::KatakanaReading;
+
+    use super::*;
+
+    #[test]
+    fn test_rule_based_readings() {
+        let overrides = HashMap::new();
+        assert_eq!(word_to_katakana("sky", &overrides), "スカイ");
+        assert_eq!(word_to_katakana("fly", &overrides), "フライ");
+        assert_eq!(word_to_katakana("happy", &overrides), "ハッピー");
+        assert_eq!(word_to_katakana("dance", &overrides), "ダンス");
+        assert_eq!(word_to_katakana("stone", &overrides), "ストーン");
+        assert_eq!(word_to_katakana("jump", &overrides), "ジャンプ");
+        assert_eq!(word_to_katakana("tennis", &overrides), "テニス");
+        assert_eq!(word_to_katakana("face", &overrides), "フェース");
+        assert_eq!(word_to_katakana("HTML", &overrides), "エイチティーエムエル");
+    }
+
+    #[test]
+    fn test_convert_with_overrides() {
+        let config = KatakanaConfig {
+            enable: true,
+            overrides: vec![KatakanaReading {
+                word: "Macarron".to_string(),
+                reading: "マカロン".to_string(),
+            }],
+        };
+        assert_eq!(convert("Macarronの新しいstreamです", &config), "マカロンの新しいストリームです");
+    }
+}
@@ -0,0 +1,22 @@
+use crate::config::text_processing;
+
+pub mod katakana;
+pub mod normalizer;
+
+/// process text by configured stages before it is sent to voice engine for synthesis
+pub async fn process(text: String) -> String {
+    let config = {
+        let manager = text_processing::TEXT_PROCESSING_CONFIG_MANAGER.read().await;
+        manager.get_config()
+    };
+    let mut text = text;
+    if config.normalizer.enable {
+        text = normalizer::normalize(&*text, &config.normalizer);
+        log::debug!("Normalized text: {}", text.clone());
+    }
+    if config.katakana.enable {
+        text = katakana::convert(&*text, &config.katakana);
+        log::debug!("Converted katakana text: {}", text.clone());
+    }
+    text
+}
@@ -0,0 +1,333 @@
+use lazy_static::lazy_static;
+use tauri::regex::{Captures, Regex};
+
+use crate::config::text_processing::NormalizerConfig;
+
+lazy_static! {
+    static ref MD_CODE_BLOCK: Regex = Regex::new(r"```[^\n]*\n?([\s\S]*?)```").unwrap();
+    static ref MD_INLINE_CODE: Regex = Regex::new(r"`([^`]*)`").unwrap();
+    static ref MD_IMAGE_LINK: Regex = Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap();
+    static ref MD_EMPHASIS: Regex = Regex::new(r"(\*{1,3}|~~|__)([^*~_]+?)(\*{1,3}|~~|__)").unwrap();
+    static ref MD_LINE_PREFIX: Regex = Regex::new(r"(?m)^\s*(#{1,6}\s+|>\s?|[-*+]\s+|\d+\.\s+)").unwrap();
+    static ref URL: Regex = Regex::new(r"(https?://|www\.)[\w\-.~:/?#\[\]@!$&'()*+,;=%]+").unwrap();
+    static ref EMOJI: Regex = Regex::new(r"[\x{1F000}-\x{1FAFF}\x{2600}-\x{27BF}\x{2B00}-\x{2BFF}\x{FE0F}\x{200D}]").unwrap();
+    static ref DATE: Regex = Regex::new(r"(\d{4})[/\-](\d{1,2})[/\-](\d{1,2})").unwrap();
+    static ref TIME: Regex = Regex::new(r"(\d{1,2}):(\d{2})(?::(\d{2}))?").unwrap();
+    static ref CURRENCY: Regex = Regex::new(r"([$＄¥￥€£])\s?(\d[\d,]*(?:\.\d+)?)").unwrap();
+    static ref UNIT: Regex = Regex::new(r"(\d+(?:\.\d+)?)\s?(km/h|[A-Za-z]+|%|％|℃|°C)").unwrap();
+    static ref NUMBER: Regex = Regex::new(r"\d[\d,]*(?:\.\d+)?").unwrap();
+    // abbreviations are only matched as whole words, "No." only before a number
+    static ref ABBREVIATION: Regex = Regex::new(r"(^|[^A-Za-z])(e\.g\.|i\.e\.|etc\.|vs\.|Mr\.|Ms\.|Dr\.|Ver\.|w/o|No\.(?:\s?\d))").unwrap();
+    static ref LAUGH: Regex = Regex::new(r"(^|[^A-Za-z])[wｗ]{2,}").unwrap();
+    static ref LAUGH_SINGLE: Regex = Regex::new(r"([\p{Hiragana}\p{Katakana}\p{Han}])[wｗ](\s|$)").unwrap();
+}
+
+const EMOJI_READINGS: [(&str, &str); 8] = [
+    ("😂", "笑"),
+    ("🤣", "笑"),
+    ("😭", "泣"),
+    ("👍", "いいね"),
+    ("❤", "ハート"),
+    ("🎉", "おめでとう"),
+    ("🙏", "お願い"),
+    ("💦", "汗"),
+];
+
+const ABBREVIATIONS: [(&str, &str); 9] = [
+    ("e.g.", "例えば"),
+    ("i.e.", "つまり"),
+    ("etc.", "など"),
+    ("vs.", "バーサス"),
+    ("Mr.", "ミスター"),
+    ("Ms.", "ミズ"),
+    ("Dr.", "ドクター"),
+    ("Ver.", "バージョン"),
+    ("w/o", "なし"),
+];
+
+const SYMBOLS: [(&str, &str); 2] = [
+    ("&", "アンド"),
+    ("@", "アット"),
+];
+
+const UNITS: [(&str, &str); 28] = [
+    ("km/h", "キロメートル毎時"),
+    ("km", "キロメートル"),
+    ("cm", "センチメートル"),
+    ("mm", "ミリメートル"),
+    ("m", "メートル"),
+    ("kg", "キログラム"),
+    ("mg", "ミリグラム"),
+    ("g", "グラム"),
+    ("ml", "ミリリットル"),
+    ("mL", "ミリリットル"),
+    ("l", "リットル"),
+    ("L", "リットル"),
+    ("%", "パーセント"),
+    ("％", "パーセント"),
+    ("℃", "度"),
+    ("°C", "度"),
+    ("TB", "テラバイト"),
+    ("GB", "ギガバイト"),
+    ("MB", "メガバイト"),
+    ("KB", "キロバイト"),
+    ("GHz", "ギガヘルツ"),
+    ("MHz", "メガヘルツ"),
+    ("Hz", "ヘルツ"),
+    ("fps", "エフピーエス"),
+    ("ms", "ミリ秒"),
+    ("sec", "秒"),
+    ("min", "分"),
+    ("h", "時間"),
+];
+
+const KANJI_DIGITS: [&str; 10] = ["〇", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+
+/// reading of number less than 10000, e.g. 1234 => 千二百三十四
+fn small_number_to_kanji(n: u64) -> String {
+    let mut result = String::new();
+    for (unit, name) in [(1000, "千"), (100, "百"), (10, "十")] {
+        let digit = (n / unit) % 10;
+        if digit > 0 {
+            // 一 is omitted before 千, 百 and 十
+            if digit > 1 {
+                result.push_str(KANJI_DIGITS[digit as usize]);
+            }
+            result.push_str(name);
+        }
+    }
+    let digit = n % 10;
+    if digit > 0 {
+        result.push_str(KANJI_DIGITS[digit as usize]);
+    }
+    result
+}
+
+/// reading of integer by kanji numerals grouped by 万, 億 and 兆
+pub fn number_to_kanji(n: u64) -> String {
+    if n == 0 {
+        return "ゼロ".to_string();
+    }
+    let mut result = String::new();
+    for (unit, name) in [(1_0000_0000_0000, "兆"), (1_0000_0000, "億"), (1_0000, "万"), (1, "")] {
+        let group = (n / unit) % 1_0000;
+        if group > 0 {
+            result.push_str(&small_number_to_kanji(group));
+            result.push_str(name);
+        }
+    }
+    result
+}
+
+fn digits_to_kanji(digits: &str) -> String {
+    digits.chars()
+        .filter_map(|ch| ch.to_digit(10))
+        .map(|digit| KANJI_DIGITS[digit as usize])
+        .collect()
+}
+
+/// reading of number text like "1,234.5"
+fn expand_number(number: &str) -> String {
+    let number = number.replace(',', "");
+    let (integer, decimal) = match number.split_once('.') {
+        Some((integer, decimal)) => (integer, Some(decimal)),
+        None => (number.as_str(), None)
+    };
+    // numbers with leading zero or too long are read digit by digit, like ids or phone numbers
+    let mut result = if (integer.len() > 1 && integer.starts_with('0')) || integer.len() > 16 {
+        digits_to_kanji(integer)
+    } else {
+        number_to_kanji(integer.parse::<u64>().unwrap_or(0))
+    };
+    if let Some(decimal) = decimal {
+        result.push('点');
+        result.push_str(&digits_to_kanji(decimal));
+    }
+    result
+}
+
+fn strip_markdown(text: &str) -> String {
+    let text = MD_CODE_BLOCK.replace_all(text, "$1");
+    let text = MD_INLINE_CODE.replace_all(&text, "$1");
+    let text = MD_IMAGE_LINK.replace_all(&text, "$1");
+    let text = MD_EMPHASIS.replace_all(&text, "$2");
+    MD_LINE_PREFIX.replace_all(&text, "").to_string()
+}
+
+fn replace_urls(text: &str) -> String {
+    URL.replace_all(text, "ユーアールエル").to_string()
+}
+
+fn replace_emoji(text: &str) -> String {
+    let mut text = text.to_string();
+    for (emoji, reading) in EMOJI_READINGS {
+        text = text.replace(emoji, reading);
+    }
+    EMOJI.replace_all(&text, "").to_string()
+}
+
+fn expand_abbreviations(text: &str) -> String {
+    ABBREVIATION.replace_all(text, |caps: &Captures| {
+        let abbreviation = &caps[2];
+        let reading = match abbreviation.strip_prefix("No.") {
+            Some(number) => format!("ナンバー{}", number.trim_start()),
+            None => ABBREVIATIONS.iter()
+                .find(|(word, _)| *word == abbreviation)
+                .map(|(_, reading)| reading.to_string())
+                .unwrap_or_else(|| abbreviation.to_string())
+        };
+        format!("{}{}", &caps[1], reading)
+    }).to_string()
+}
+
+fn expand_symbols(text: &str) -> String {
+    let mut text = text.to_string();
+    for (symbol, reading) in SYMBOLS {
+        text = text.replace(symbol, reading);
+    }
+    text
+}
+
+fn expand_date_time(text: &str) -> String {
+    let text = DATE.replace_all(text, |caps: &Captures| {
+        format!("{}年{}月{}日", &caps[1], &caps[2], &caps[3])
+    });
+    TIME.replace_all(&text, |caps: &Captures| {
+        let mut result = format!("{}時", &caps[1]);
+        if &caps[2] != "00" || caps.get(3).is_some() {
+            result.push_str(&format!("{}分", &caps[2]));
+        }
+        if let Some(second) = caps.get(3) {
+            result.push_str(&format!("{}秒", second.as_str()));
+        }
+        result
+    }).to_string()
+}
+
+fn expand_currencies(text: &str) -> String {
+    CURRENCY.replace_all(text, |caps: &Captures| {
+        let unit = match &caps[1] {
+            "$" | "＄" => "ドル",
+            "¥" | "￥" => "円",
+            "€" => "ユーロ",
+            _ => "ポンド",
+        };
+        format!("{}{}", &caps[2], unit)
+    }).to_string()
+}
+
+fn expand_units(text: &str) -> String {
+    UNIT.replace_all(text, |caps: &Captures| {
+        match UNITS.iter().find(|(unit, _)| *unit == &caps[2]) {
+            Some((_, reading)) => format!("{}{}", &caps[1], reading),
+            None => caps[0].to_string()
+        }
+    }).to_string()
+}
+
+fn expand_numbers(text: &str) -> String {
+    let text = expand_date_time(text);
+    let text = expand_currencies(&text);
+    let text = expand_units(&text);
+    NUMBER.replace_all(&text, |caps: &Captures| expand_number(&caps[0])).to_string()
+}
+
+/// net slang "www" means laughing
+fn replace_laugh(text: &str) -> String {
+    let text = LAUGH.replace_all(text, "${1}わらわら");
+    LAUGH_SINGLE.replace_all(&text, "${1}わら${2}").to_string()
+}
+
+fn collapse_repeats(text: &str, max_repeat: usize) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last: Option<char> = None;
+    let mut count = 0;
+    for ch in text.chars() {
+        if Some(ch) == last {
+            count += 1;
+        } else {
+            last = Some(ch);
+            count = 1;
+        }
+        if count <= max_repeat {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+/// normalize raw text like chat messages into speakable japanese text
+pub fn normalize(text: &str, config: &NormalizerConfig) -> String {
+    let mut text = text.to_string();
+    if config.strip_markdown {
+        text = strip_markdown(&text);
+    }
+    if config.replace_urls {
+        text = replace_urls(&text);
+    }
+    if config.replace_emoji {
+        text = replace_emoji(&text);
+    }
+    if config.expand_abbreviations {
+        text = expand_abbreviations(&text);
+    }
+    if config.expand_symbols {
+        text = expand_symbols(&text);
+    }
+    if config.expand_numbers {
+        text = expand_numbers(&text);
+    }
+    if config.replace_laugh {
+        text = replace_laugh(&text);
+    }
+    if config.max_repeat > 0 {
+        text = collapse_repeats(&text, config.max_repeat);
+    }
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> NormalizerConfig {
+        NormalizerConfig {
+            enable: true,
+            ..NormalizerConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_number_to_kanji() {
+        assert_eq!(number_to_kanji(0), "ゼロ");
+        assert_eq!(number_to_kanji(1234), "千二百三十四");
+        assert_eq!(number_to_kanji(10_0020), "十万二十");
+        assert_eq!(number_to_kanji(3_0000_0001), "三億一");
+        assert_eq!(expand_number("1,000.25"), "千点二五");
+        assert_eq!(expand_number("0120"), "〇一二〇");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let config = test_config();
+        assert_eq!(normalize("**明日**は2023/4/1です", &config), "明日は二千二十三年四月一日です");
+        assert_eq!(normalize("12:30に集合", &config), "十二時三十分に集合");
+        assert_eq!(normalize("$5と5kgと50%", &config), "五ドルと五キログラムと五十パーセント");
+        assert_eq!(normalize("見て https://example.com/a?b=1 😂", &config), "見て ユーアールエル 笑");
+        assert_eq!(normalize("草wwwww", &config), "草わらわら");
+        assert_eq!(normalize("すごいw", &config), "すごいわら");
+        assert_eq!(normalize("えーーーーーー！！！！", &config), "えーーー！！！");
+    }
+
+    #[test]
+    fn test_abbreviations_by_word() {
+        let config = test_config();
+        assert_eq!(normalize("Dr. Smith vs. Mr.T", &config), "ドクター Smith バーサス ミスターT");
+        assert_eq!(normalize("No.1になる", &config), "ナンバー一になる");
+        // not an abbreviation within a word or without a number
+        assert_eq!(normalize("Say No. Cheers.", &config), "Say No. Cheers.");
+        assert_eq!(normalize("Q&A @home", &config), "Q&A @home");
+        let config = NormalizerConfig { expand_symbols: true, replace_laugh: false, ..test_config() };
+        assert_eq!(normalize("Q&A @home www", &config), "QアンドA アットhome www");
+    }
+}
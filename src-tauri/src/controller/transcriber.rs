@@ -0,0 +1,315 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use rodio::{Decoder, Source};
+use samplerate::{ConverterType, Samplerate};
+
+use crate::common::{app, constants};
+use crate::config::voice_recognition;
+use crate::controller::errors::ProgramError;
+use crate::controller::recognizer;
+use crate::controller::recognizer::{RecognizedSegment, SpeechRecognizer};
+
+// whisper decodes at most 30s of audio in one pass
+const WINDOW_MS: usize = 30_000;
+// windows are cut at the quietest frame of their last seconds so that words are not split
+const CUT_SEARCH_MS: usize = 5_000;
+const CUT_FRAME_MS: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, strum_macros::EnumString, serde::Serialize, serde::Deserialize)]
+pub enum TranscriptFormat {
+    #[strum(serialize = "txt")]
+    #[serde(rename = "txt")]
+    Text,
+    #[strum(serialize = "srt")]
+    #[serde(rename = "srt")]
+    Srt,
+    #[strum(serialize = "vtt")]
+    #[serde(rename = "vtt")]
+    Vtt,
+}
+
+impl TranscriptFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Text => "txt",
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Vtt => "vtt",
+        }
+    }
+
+    fn format(&self, segments: &[RecognizedSegment]) -> String {
+        match self {
+            TranscriptFormat::Text => to_text(segments),
+            TranscriptFormat::Srt => to_srt(segments),
+            TranscriptFormat::Vtt => to_vtt(segments),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TranscribeProgress {
+    pub file: String,
+    pub processed_ms: i64,
+    pub total_ms: i64,
+}
+
+// frames resampled in one pass while decoding, 1s of 48kHz audio
+const RESAMPLE_CHUNK: usize = 48_000;
+
+/// downmix interleaved samples into mono and resample to `target_rate` chunk by chunk,
+/// so that the whole interleaved input is never held in memory
+fn downmix_and_resample(samples: impl Iterator<Item=f32>,
+                        channels: u16,
+                        sample_rate: u32,
+                        target_rate: u32) -> Result<Vec<f32>, ProgramError> {
+    if channels == 0 {
+        return Err(ProgramError::from(format!("unsupported input channel value: {}", channels)));
+    }
+    let converter = if sample_rate == target_rate {
+        None
+    } else {
+        log::debug!("Convert audio from rate {} to rate {}", sample_rate, target_rate);
+        Some(Samplerate::new(ConverterType::SincBestQuality, sample_rate, target_rate, 1)?)
+    };
+
+    let mut output = vec![];
+    let mut chunk = Vec::with_capacity(RESAMPLE_CHUNK);
+    let mut frame_sum = 0.0;
+    let mut frame_len = 0;
+    for sample in samples {
+        frame_sum += sample;
+        frame_len += 1;
+        if frame_len < channels {
+            continue;
+        }
+        chunk.push(frame_sum / channels as f32);
+        frame_sum = 0.0;
+        frame_len = 0;
+        if chunk.len() == RESAMPLE_CHUNK {
+            match &converter {
+                Some(converter) => output.extend(converter.process(&chunk)?),
+                None => output.extend_from_slice(&chunk),
+            }
+            chunk.clear();
+        }
+    }
+    match &converter {
+        Some(converter) => output.extend(converter.process_last(&chunk)?),
+        None => output.extend_from_slice(&chunk),
+    }
+    Ok(output)
+}
+
+/// decode wav, mp3, flac or ogg file into mono samples of `target_rate`
+fn decode_file(file: &Path, target_rate: u32) -> Result<Vec<f32>, ProgramError> {
+    let source = Decoder::new(BufReader::new(File::open(file)?))?;
+    let channels = source.channels();
+    let sample_rate = source.sample_rate();
+    downmix_and_resample(source.convert_samples(), channels, sample_rate, target_rate)
+}
+
+/// middle of the frame with least energy in samples\[from..to], or `to` if no frame fits
+fn quietest_cut(samples: &[f32], from: usize, to: usize, frame: usize) -> usize {
+    let mut cut = to;
+    let mut min_energy = f32::MAX;
+    let mut pos = from;
+    while pos + frame <= to {
+        let energy: f32 = samples[pos..pos + frame].iter().map(|s| s * s).sum();
+        if energy < min_energy {
+            min_energy = energy;
+            cut = pos + frame / 2;
+        }
+        pos += frame;
+    }
+    cut
+}
+
+/// split samples into ranges of at most `window` samples, every range except the last one ends at
+/// the quietest frame within its last `search` samples
+fn split_windows(samples: &[f32], window: usize, search: usize, frame: usize) -> Vec<(usize, usize)> {
+    let mut windows = vec![];
+    let mut start = 0;
+    while start < samples.len() {
+        let mut end = (start + window).min(samples.len());
+        if end < samples.len() {
+            let from = end.saturating_sub(search).max(start + frame);
+            end = quietest_cut(samples, from, end, frame);
+        }
+        windows.push((start, end));
+        start = end;
+    }
+    windows
+}
+
+fn samples_to_ms(samples: usize, sample_rate: u32) -> i64 {
+    samples as i64 * 1000 / sample_rate as i64
+}
+
+/// recognize long mono samples window by window, segment times are shifted to the whole samples
+async fn transcribe_samples(recognizer: &dyn SpeechRecognizer,
+                            samples: &[f32],
+                            on_progress: impl Fn(i64, i64)) -> Result<Vec<RecognizedSegment>, ProgramError> {
+    let sample_rate = recognizer.sample_rate();
+    let ms_to_samples = |ms: usize| ms * sample_rate as usize / 1000;
+    let windows = split_windows(samples,
+                                ms_to_samples(WINDOW_MS),
+                                ms_to_samples(CUT_SEARCH_MS),
+                                ms_to_samples(CUT_FRAME_MS).max(1));
+    let total_ms = samples_to_ms(samples.len(), sample_rate);
+
+    let mut segments = vec![];
+    for (start, end) in windows {
+        let offset_ms = samples_to_ms(start, sample_rate);
+        let window = samples[start..end].to_vec();
+        for segment in recognizer.recognize_segments(&window).await? {
+            let text = segment.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            segments.push(RecognizedSegment {
+                text: text.to_string(),
+                start_ms: offset_ms + segment.start_ms,
+                end_ms: offset_ms + segment.end_ms,
+            });
+        }
+        on_progress(samples_to_ms(end, sample_rate), total_ms);
+    }
+    Ok(segments)
+}
+
+/// timestamp like 01:02:03,456, srt uses ',' and vtt uses '.' before milliseconds
+fn format_timestamp(ms: i64, separator: char) -> String {
+    let ms = ms.max(0);
+    format!("{:02}:{:02}:{:02}{}{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            separator,
+            ms % 1000)
+}
+
+fn to_text(segments: &[RecognizedSegment]) -> String {
+    let mut text = String::new();
+    for segment in segments {
+        text.push_str(&segment.text);
+        text.push('\n');
+    }
+    text
+}
+
+fn to_srt(segments: &[RecognizedSegment]) -> String {
+    let mut srt = String::new();
+    for (i, segment) in segments.iter().enumerate() {
+        srt.push_str(&format!("{}\n{} --> {}\n{}\n\n",
+                              i + 1,
+                              format_timestamp(segment.start_ms, ','),
+                              format_timestamp(segment.end_ms, ','),
+                              segment.text));
+    }
+    srt
+}
+
+fn to_vtt(segments: &[RecognizedSegment]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for segment in segments {
+        vtt.push_str(&format!("{} --> {}\n{}\n\n",
+                              format_timestamp(segment.start_ms, '.'),
+                              format_timestamp(segment.end_ms, '.'),
+                              segment.text));
+    }
+    vtt
+}
+
+/// transcribe audio file by configured recognizer and write transcripts of given formats
+/// into `output_dir`, or the directory of audio file if not given, returns paths of written files
+pub async fn transcribe_file(file: String,
+                             formats: Vec<TranscriptFormat>,
+                             output_dir: Option<String>) -> Result<Vec<String>, ProgramError> {
+    let config = {
+        let manager =
+            voice_recognition::VOICE_REC_CONFIG_MANAGER.read().await;
+        manager.get_config()
+    };
+    let recognizer = recognizer::get_recognizer(&config)?
+        .ok_or("Voice recognition is not enabled")?;
+
+    let input = PathBuf::from(&file);
+    let target_rate = recognizer.sample_rate();
+    let decode_input = input.clone();
+    let samples = tauri::async_runtime::spawn_blocking(move || decode_file(&decode_input, target_rate)).await??;
+    log::debug!("Transcribe file {} of {} samples by {}", file, samples.len(), recognizer.name());
+
+    let segments = transcribe_samples(&*recognizer, &samples, |processed_ms, total_ms| {
+        app::silent_emit_all(constants::event::ON_FILE_TRANSCRIBE_PROGRESS, TranscribeProgress {
+            file: file.clone(),
+            processed_ms,
+            total_ms,
+        });
+    }).await?;
+
+    let output_dir = match output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => input.parent().map(Path::to_path_buf).unwrap_or_default()
+    };
+    std::fs::create_dir_all(&output_dir)?;
+    let stem = input.file_stem()
+        .ok_or("Unable to get file name of transcribed file")?
+        .to_string_lossy()
+        .to_string();
+
+    let formats = if formats.is_empty() { vec![TranscriptFormat::Text] } else { formats };
+    let mut outputs = vec![];
+    for format in formats {
+        let output = output_dir.join(format!("{}.{}", stem, format.extension()));
+        std::fs::write(&output, format.format(&segments))?;
+        outputs.push(output.to_string_lossy().to_string());
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, start_ms: i64, end_ms: i64) -> RecognizedSegment {
+        RecognizedSegment { text: text.to_string(), start_ms, end_ms }
+    }
+
+    #[test]
+    fn test_split_windows_at_quiet_frame() {
+        let mut samples = vec![0.5f32; 100];
+        // silence around 85..90 is within the last 20 samples of the first window
+        for sample in &mut samples[85..90] {
+            *sample = 0.0;
+        }
+        let windows = split_windows(&samples, 95, 20, 5);
+        assert_eq!(windows, vec![(0, 87), (87, 100)]);
+
+        let windows = split_windows(&samples, 200, 20, 5);
+        assert_eq!(windows, vec![(0, 100)]);
+    }
+
+    #[test]
+    fn test_downmix_and_resample() {
+        let stereo = (0..RESAMPLE_CHUNK + 10).flat_map(|_| [0.2f32, 0.4f32]);
+        let mono = downmix_and_resample(stereo, 2, 16000, 16000).unwrap();
+        assert_eq!(mono.len(), RESAMPLE_CHUNK + 10);
+        assert!(mono.iter().all(|sample| (sample - 0.3).abs() < 1e-6));
+
+        let samples = vec![0.0f32; 32000 * 3];
+        let resampled = downmix_and_resample(samples.into_iter(), 1, 32000, 16000).unwrap();
+        assert!((resampled.len() as i64 - 16000 * 3).abs() <= 16);
+    }
+
+    #[test]
+    fn test_subtitles() {
+        let segments = vec![segment("Hello", 0, 1500), segment("world", 3_723_004, 3_725_000)];
+        assert_eq!(to_text(&segments), "Hello\nworld\n");
+        assert_eq!(to_srt(&segments),
+                   "1\n00:00:00,000 --> 00:00:01,500\nHello\n\n2\n01:02:03,004 --> 01:02:05,000\nworld\n\n");
+        assert_eq!(to_vtt(&segments),
+                   "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello\n\n01:02:03.004 --> 01:02:05.000\nworld\n\n");
+    }
+}
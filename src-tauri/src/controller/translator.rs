@@ -1,26 +1,119 @@
-use crate::config::auto_translation;
-use crate::controller::translators::baidu_translation;
-
-pub async fn translate(text: String) -> Option<String> {
-    let manager = auto_translation::AUTO_TRANS_CONFIG_MANAGER.lock().await;
-    let config = manager.get_config();
-    let (translate, by_baidu) = config.translated_by_baidu();
-    if !translate {
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::config::{auto_translation, glossary};
+use crate::config::auto_translation::AutoTranslateTool;
+use crate::controller::errors::ProgramError;
+use crate::controller::translators::{baidu_translation, glossary as glossary_mask, language};
+use crate::controller::translators::language::Language;
+
+#[derive(Debug, Clone)]
+pub struct Translation {
+    pub text: String,
+    // name of the translation service which produced this result
+    pub translator: String,
+}
+
+async fn translate_by(tool: &AutoTranslateTool,
+                      text: String,
+                      detected: Language,
+                      deadline: Instant) -> Result<String, ProgramError> {
+    match tool {
+        AutoTranslateTool::Baidu(baidu_config) => {
+            log::info!("Translate by baidu, text: {}", text.clone());
+            let (from, _) = tool.languages();
+            let baidu_config = match detected_from(&*from, detected) {
+                Some(code) => baidu_config.with_from(code),
+                None => baidu_config.clone()
+            };
+            baidu_translation::translate(&baidu_config, text, deadline).await
+        }
+    }
+}
+
+/// detected language replacing source language configured as auto,
+/// none if text is not detected confidently and the translator should detect it by itself
+fn detected_from(from: &str, detected: Language) -> Option<&'static str> {
+    if from == "auto" {
+        detected.baidu_code()
+    } else {
+        None
+    }
+}
+
+/// whether text is detected confidently as target language
+fn in_target_language(detected: Language, to: &str) -> bool {
+    detected != Language::Unknown && detected == Language::from_baidu_code(to)
+}
+
+/// translate text by configured translators in order, if one of them failed or timeout,
+/// try the next one; return None if translation is not enabled or text is already
+/// in target language, and return error if all translators are failed
+pub async fn translate(text: String) -> Result<Option<Translation>, ProgramError> {
+    let config = {
+        let manager = auto_translation::AUTO_TRANS_CONFIG_MANAGER.lock().await;
+        manager.get_config()
+    };
+    let tools = config.translators();
+    if tools.is_empty() {
         log::info!("Translate not enabled, skip");
-        return None;
+        return Ok(None);
+    }
+    let detected = language::detect(&*text);
+    log::debug!("Detected language of text: {:?}", detected);
+
+    let glossary_config = {
+        let manager = glossary::GLOSSARY_CONFIG_MANAGER.read().await;
+        manager.get_config()
+    };
+    // mask glossary terms so that translators won't mangle them
+    let masked = glossary_mask::mask(&*text, &glossary_config);
+    if masked.is_masked() {
+        log::debug!("Masked glossary terms of text: {}", masked.text.clone());
     }
-    if let Some(baidu_config) = by_baidu {
-        log::info!("Translate by baidu, text: {}", text.clone());
-        let result = baidu_translation::translate(&baidu_config, text)
-            .await;
+
+    let timeout = Duration::from_millis(config.get_timeout_ms());
+    for tool in tools {
+        let (_, to) = tool.languages();
+        if in_target_language(detected, &*to) {
+            log::info!("Text is already in target language {}, skip translation", to);
+            return Ok(None);
+        }
+        let deadline = Instant::now() + timeout;
+        let result = tokio::time::timeout_at(
+            deadline, translate_by(&tool, masked.text.clone(), detected, deadline)).await;
         match result {
-            Ok(translated) => {
-                return Some(translated);
+            Ok(Ok(translated)) => {
+                return Ok(Some(Translation {
+                    text: glossary_mask::restore(&*translated, &masked),
+                    translator: tool.name(),
+                }));
             }
-            Err(err) => {
-                log::error!("Failed to translate text, err: {}", err);
+            Ok(Err(err)) => {
+                log::error!("Failed to translate text by {}, err: {}", tool.name(), err);
+            }
+            Err(_) => {
+                log::error!("Translate text by {} timeout after {}ms", tool.name(), timeout.as_millis());
             }
         }
     }
-    None
+    Err(ProgramError::from("all translators failed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin_text_translated_by_auto_detection() {
+        let detected = language::detect("Bonjour, je suis très content de vous voir");
+        assert!(!in_target_language(detected, "en"));
+        assert_eq!(detected_from("auto", detected), None);
+
+        let detected = language::detect("今日はいい天気ですね");
+        assert!(in_target_language(detected, "jp"));
+        assert_eq!(detected_from("auto", detected), Some("jp"));
+        assert_eq!(detected_from("zh", detected), None);
+    }
 }
@@ -1,29 +1,226 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use rand::Rng;
 use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::StatusCode;
+use tokio::time::Instant;
 
 use crate::config::auto_translation::TranslateByBaidu;
 use crate::controller::errors::{CommonError, ProgramError};
 
-pub async fn translate(config: &TranslateByBaidu, text: String) -> Result<String, ProgramError> {
-    log::debug!("Translate text by baidu api, source: {}", text);
+const MAX_RETRIES: u32 = 3;
+const RETRY_INTERVAL_MS: u64 = 1000;
+
+/// errors returned by baidu translation api with http status 200,
+/// refer: https://fanyi-api.baidu.com/doc/21
+#[derive(Debug, Clone, PartialEq)]
+pub enum BaiduTranslationError {
+    /// 52001, request timeout
+    Timeout,
+    /// 52002, baidu system error
+    SystemError,
+    /// 52003, unauthorized user, check app id
+    Unauthorized,
+    /// 54000, required param is empty
+    EmptyParam,
+    /// 54001, sign error, check app id and secret
+    SignError,
+    /// 54003, access frequency limited
+    RateLimited,
+    /// 54004, account balance is insufficient
+    InsufficientBalance,
+    /// 54005, long query requested too frequently
+    LongQueryRateLimited,
+    /// 58000, client ip is not allowed
+    IllegalClientIp,
+    /// 58001, translation language direction is not supported
+    UnsupportedLanguage,
+    /// 58002, service is closed
+    ServiceClosed,
+    /// 90107, authentication is not passed or not effective
+    AuthenticationFailed,
+    /// response contains no error code but no translation result either
+    EmptyResult,
+    Unknown { code: String, message: String },
+}
+
+impl BaiduTranslationError {
+    pub fn from_code(code: &str, message: String) -> Self {
+        match code {
+            "52001" => BaiduTranslationError::Timeout,
+            "52002" => BaiduTranslationError::SystemError,
+            "52003" => BaiduTranslationError::Unauthorized,
+            "54000" => BaiduTranslationError::EmptyParam,
+            "54001" => BaiduTranslationError::SignError,
+            "54003" => BaiduTranslationError::RateLimited,
+            "54004" => BaiduTranslationError::InsufficientBalance,
+            "54005" => BaiduTranslationError::LongQueryRateLimited,
+            "58000" => BaiduTranslationError::IllegalClientIp,
+            "58001" => BaiduTranslationError::UnsupportedLanguage,
+            "58002" => BaiduTranslationError::ServiceClosed,
+            "90107" => BaiduTranslationError::AuthenticationFailed,
+            _ => BaiduTranslationError::Unknown {
+                code: code.to_string(),
+                message,
+            }
+        }
+    }
+
+    /// baidu suggests retrying the same request on these errors
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BaiduTranslationError::Timeout |
+            BaiduTranslationError::SystemError |
+            BaiduTranslationError::RateLimited |
+            BaiduTranslationError::LongQueryRateLimited => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for BaiduTranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BaiduTranslationError::Timeout => write!(f, "baidu translation: request timeout"),
+            BaiduTranslationError::SystemError => write!(f, "baidu translation: system error"),
+            BaiduTranslationError::Unauthorized => write!(f, "baidu translation: unauthorized user"),
+            BaiduTranslationError::EmptyParam => write!(f, "baidu translation: required param is empty"),
+            BaiduTranslationError::SignError => write!(f, "baidu translation: sign error, check app id and secret"),
+            BaiduTranslationError::RateLimited => write!(f, "baidu translation: access frequency limited"),
+            BaiduTranslationError::InsufficientBalance => write!(f, "baidu translation: insufficient account balance"),
+            BaiduTranslationError::LongQueryRateLimited => write!(f, "baidu translation: long query requested too frequently"),
+            BaiduTranslationError::IllegalClientIp => write!(f, "baidu translation: illegal client ip"),
+            BaiduTranslationError::UnsupportedLanguage => write!(f, "baidu translation: unsupported language direction"),
+            BaiduTranslationError::ServiceClosed => write!(f, "baidu translation: service is closed"),
+            BaiduTranslationError::AuthenticationFailed => write!(f, "baidu translation: authentication failed"),
+            BaiduTranslationError::EmptyResult => write!(f, "baidu translation: empty translation result"),
+            BaiduTranslationError::Unknown { code, message } => {
+                write!(f, "baidu translation: error code {}, message: {}", code, message)
+            }
+        }
+    }
+}
+
+impl Error for BaiduTranslationError {}
+
+/// baidu suggests a random number in range \[32768, 65536) as salt
+fn gen_salt() -> String {
+    rand::thread_rng().gen_range(32768..65536).to_string()
+}
+
+/// parse json response of baidu translation api, every line of source text is translated
+/// into a separate entry of `trans_result`, join them back with line breaks
+fn parse_response(json: &serde_json::Value) -> Result<String, BaiduTranslationError> {
+    let error_code = &json["error_code"];
+    // error code may be returned as string or number
+    let error_code = if let Some(code) = error_code.as_str() {
+        Some(code.to_string())
+    } else {
+        error_code.as_i64().map(|code| code.to_string())
+    };
+    if let Some(code) = error_code {
+        // 52000 means success
+        if code != "52000" {
+            let message = json["error_msg"].as_str().unwrap_or_default().to_string();
+            return Err(BaiduTranslationError::from_code(&*code, message));
+        }
+    }
+
+    let results = json["trans_result"].as_array()
+        .ok_or(BaiduTranslationError::EmptyResult)?;
+    let lines: Vec<&str> = results.iter()
+        .filter_map(|result| result["dst"].as_str())
+        .collect();
+    if lines.is_empty() {
+        return Err(BaiduTranslationError::EmptyResult);
+    }
+    Ok(lines.join("\n"))
+}
+
+async fn request_translate(config: &TranslateByBaidu, text: &str) -> Result<String, ProgramError> {
     let client = reqwest::Client::new();
     let mut headers = HeaderMap::new();
     headers.insert("Content-Type", HeaderValue::from_static("application/x-www-form-urlencoded"));
     headers.insert("User-Agent", HeaderValue::from_static("Mozilla/5.0"));
+    let salt = gen_salt();
     let res: reqwest::Response = client
         .post(config.to_owned().get_api())
-        .form(&config.to_owned().build_params(&text))
+        .form(&config.to_owned().build_params(text, &*salt))
         .headers(headers)
         .send()
         .await
         .map_err(ProgramError::from)?;
     if res.status() == StatusCode::OK {
         let json: serde_json::Value = res.json().await.map_err(ProgramError::from)?;
-        let result = json["trans_result"][0]["dst"].as_str().unwrap();
-        log::debug!("Translated text by baidu api, result: {}", result);
-        Ok(result.to_string())
+        Ok(parse_response(&json)?)
     } else {
         Err(ProgramError::from(
-            CommonError::from_http_error( res.status(),res.text().await?)))
+            CommonError::from_http_error(res.status(), res.text().await?)))
+    }
+}
+
+/// translate text and retry on retryable errors, a retry is given up if it can't start before
+/// `deadline` so that the error is returned in time for the next translator
+pub async fn translate(config: &TranslateByBaidu, text: String, deadline: Instant) -> Result<String, ProgramError> {
+    log::debug!("Translate text by baidu api, source: {}", text);
+    let mut retries = 0;
+    loop {
+        let result = request_translate(config, &*text).await;
+        if let Err(err) = &result {
+            let retryable = err.source()
+                .and_then(|source| source.downcast_ref::<BaiduTranslationError>())
+                .map(|source| source.is_retryable())
+                .unwrap_or(false);
+            let interval = Duration::from_millis(RETRY_INTERVAL_MS * (retries + 1) as u64);
+            if retryable && retries < MAX_RETRIES && Instant::now() + interval < deadline {
+                retries += 1;
+                log::warn!("Translate by baidu api failed with err: {}, retry {} of {}",
+                    err, retries, MAX_RETRIES);
+                tokio::time::sleep(interval).await;
+                continue;
+            }
+        }
+        let result = result?;
+        log::debug!("Translated text by baidu api, result: {}", result);
+        return Ok(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multi_line_result() {
+        let json = serde_json::json!({
+            "from": "zh",
+            "to": "jp",
+            "trans_result": [
+                {"src": "你好", "dst": "こんにちは"},
+                {"src": "世界", "dst": "世界"}
+            ]
+        });
+        assert_eq!(parse_response(&json).unwrap(), "こんにちは\n世界");
+    }
+
+    #[test]
+    fn test_parse_error_code() {
+        let json = serde_json::json!({
+            "error_code": "54003",
+            "error_msg": "Invalid Access Limit"
+        });
+        let err = parse_response(&json).unwrap_err();
+        assert_eq!(err, BaiduTranslationError::RateLimited);
+        assert!(err.is_retryable());
+
+        let json = serde_json::json!({
+            "error_code": 58001,
+            "error_msg": "INVALID_TO_PARAM"
+        });
+        let err = parse_response(&json).unwrap_err();
+        assert_eq!(err, BaiduTranslationError::UnsupportedLanguage);
+        assert!(!err.is_retryable());
     }
 }
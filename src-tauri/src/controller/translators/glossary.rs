@@ -0,0 +1,127 @@
+use lazy_static::lazy_static;
+use tauri::regex::{Captures, Regex};
+
+use crate::config::glossary::GlossaryConfig;
+
+/// text with glossary terms replaced by placeholders like `{0}`,
+/// translators normally keep such placeholders untouched
+#[derive(Debug, Clone)]
+pub struct MaskedText {
+    pub text: String,
+    replacements: Vec<String>,
+}
+
+impl MaskedText {
+    pub fn is_masked(&self) -> bool {
+        !self.replacements.is_empty()
+    }
+}
+
+lazy_static! {
+    // translators may turn braces into full width ones or add spaces inside, so match them loosely
+    static ref PLACEHOLDER: Regex = Regex::new(r"[{｛]\s*(\d+)\s*[}｝]").unwrap();
+    static ref LEADING_PLACEHOLDER: Regex = Regex::new(r"^[{｛]\s*\d+\s*[}｝]").unwrap();
+}
+
+fn placeholder(index: usize) -> String {
+    format!("{{{}}}", index)
+}
+
+/// replace every glossary term and protected term in text with a placeholder,
+/// longer terms are matched first so that a term contained in another one won't break it,
+/// text looking like a placeholder already is masked as itself so that restoring keeps it
+pub fn mask(text: &str, glossary: &GlossaryConfig) -> MaskedText {
+    // (term, replacement) pairs
+    let mut terms: Vec<(&str, &str)> = glossary.terms.iter()
+        .map(|term| (term.source.as_str(), term.target.as_str()))
+        .chain(glossary.protected.iter().map(|term| (term.as_str(), term.as_str())))
+        .filter(|(term, _)| !term.is_empty())
+        .collect();
+    terms.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+    let mut replacements: Vec<String> = vec![];
+    let mut masked = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let matched = terms.iter().copied().find(|(term, _)| rest.starts_with(term))
+            .or_else(|| LEADING_PLACEHOLDER.find(rest).map(|literal| (literal.as_str(), literal.as_str())));
+        if let Some((term, replacement)) = matched {
+            let index = match replacements.iter().position(|r| r == replacement) {
+                Some(index) => index,
+                None => {
+                    replacements.push(replacement.to_string());
+                    replacements.len() - 1
+                }
+            };
+            masked.push_str(&*placeholder(index));
+            rest = &rest[term.len()..];
+        } else {
+            let ch = rest.chars().next().unwrap();
+            masked.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+    }
+
+    MaskedText {
+        text: masked,
+        replacements,
+    }
+}
+
+/// restore placeholders in translated text with the fixed target terms
+pub fn restore(translated: &str, masked: &MaskedText) -> String {
+    if !masked.is_masked() {
+        return translated.to_string();
+    }
+    PLACEHOLDER.replace_all(translated, |caps: &Captures| {
+        caps[1].parse::<usize>().ok()
+            .and_then(|index| masked.replacements.get(index))
+            .cloned()
+            .unwrap_or_else(|| caps[0].to_string())
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::glossary::GlossaryTerm;
+
+    use super::*;
+
+    fn test_glossary() -> GlossaryConfig {
+        GlossaryConfig {
+            terms: vec![
+                GlossaryTerm { source: "小马".to_string(), target: "コウマ".to_string() },
+                GlossaryTerm { source: "小马宝莉".to_string(), target: "マイリトルポニー".to_string() },
+            ],
+            protected: vec!["AIVoiceFactory".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_mask_longest_term_first() {
+        let masked = mask("小马宝莉和小马在AIVoiceFactory", &test_glossary());
+        assert_eq!(masked.text, "{0}和{1}在{2}");
+        assert!(masked.is_masked());
+    }
+
+    #[test]
+    fn test_restore_loose_placeholders() {
+        let masked = mask("小马宝莉和小马在AIVoiceFactory", &test_glossary());
+        let restored = restore("｛0｝と{ 1 }は{2}にいる", &masked);
+        assert_eq!(restored, "マイリトルポニーとコウマはAIVoiceFactoryにいる");
+    }
+
+    #[test]
+    fn test_no_terms_matched() {
+        let masked = mask("你好", &test_glossary());
+        assert!(!masked.is_masked());
+        assert_eq!(restore("{0}こんにちは", &masked), "{0}こんにちは");
+    }
+
+    #[test]
+    fn test_literal_placeholder_kept() {
+        let masked = mask("{0}和小马", &test_glossary());
+        assert_eq!(masked.text, "{0}和{1}");
+        assert_eq!(restore("{0}と{1}", &masked), "{0}とコウマ");
+    }
+}
@@ -0,0 +1,118 @@
+/// languages which can be told apart locally by the script of their characters,
+/// latin script is shared by too many languages to tell, so it is always unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Japanese,
+    Chinese,
+    Korean,
+    Unknown,
+}
+
+impl Language {
+    /// language code used by baidu translation api
+    pub fn baidu_code(&self) -> Option<&'static str> {
+        match self {
+            Language::Japanese => Some("jp"),
+            Language::Chinese => Some("zh"),
+            Language::Korean => Some("kor"),
+            Language::Unknown => None,
+        }
+    }
+
+    pub fn from_baidu_code(code: &str) -> Language {
+        match code {
+            "jp" => Language::Japanese,
+            "zh" => Language::Chinese,
+            "kor" => Language::Korean,
+            _ => Language::Unknown,
+        }
+    }
+}
+
+fn is_kana(ch: char) -> bool {
+    matches!(ch,
+        '\u{3040}'..='\u{309F}' | // hiragana
+        '\u{30A0}'..='\u{30FF}' | // katakana
+        '\u{31F0}'..='\u{31FF}' | // katakana phonetic extensions
+        '\u{FF66}'..='\u{FF9F}')  // half width katakana
+}
+
+fn is_han(ch: char) -> bool {
+    matches!(ch,
+        '\u{4E00}'..='\u{9FFF}' | // cjk unified ideographs
+        '\u{3400}'..='\u{4DBF}' | // cjk extension a
+        '\u{F900}'..='\u{FAFF}')  // cjk compatibility ideographs
+}
+
+fn is_hangul(ch: char) -> bool {
+    matches!(ch,
+        '\u{AC00}'..='\u{D7AF}' | // hangul syllables
+        '\u{1100}'..='\u{11FF}' | // hangul jamo
+        '\u{3130}'..='\u{318F}')  // hangul compatibility jamo
+}
+
+fn is_latin(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || matches!(ch, '\u{00C0}'..='\u{024F}')
+}
+
+/// detect language of text by counting characters of each script, latin script is counted
+/// by words since a latin letter carries much less than a cjk character;
+/// the script with most counts wins, and since kana only exists in japanese,
+/// any kana among kanji means the text is japanese rather than chinese;
+/// text mostly in latin script is unknown since it may be english, french, vietnamese and so on
+pub fn detect(text: &str) -> Language {
+    let mut kana = 0;
+    let mut han = 0;
+    let mut hangul = 0;
+    let mut latin = 0;
+    let mut in_latin_word = false;
+    for ch in text.chars() {
+        let latin_letter = is_latin(ch);
+        if latin_letter && !in_latin_word {
+            latin += 1;
+        }
+        in_latin_word = latin_letter;
+
+        if is_kana(ch) {
+            kana += 1;
+        } else if is_han(ch) {
+            han += 1;
+        } else if is_hangul(ch) {
+            hangul += 1;
+        }
+    }
+
+    let cjk = kana + han;
+    if cjk == 0 && hangul == 0 && latin == 0 {
+        return Language::Unknown;
+    }
+    if cjk >= hangul && cjk >= latin {
+        if kana > 0 {
+            Language::Japanese
+        } else {
+            Language::Chinese
+        }
+    } else if hangul >= latin {
+        Language::Korean
+    } else {
+        Language::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("今日はいい天気ですね"), Language::Japanese);
+        assert_eq!(detect("カタカナ"), Language::Japanese);
+        assert_eq!(detect("今天天气很好"), Language::Chinese);
+        assert_eq!(detect("오늘 날씨가 좋네요"), Language::Korean);
+        assert_eq!(detect("What a nice day"), Language::Unknown);
+        assert_eq!(detect("Quelle belle journée à Montréal"), Language::Unknown);
+        assert_eq!(detect("我在玩Minecraft"), Language::Chinese);
+        assert_eq!(detect("I really love 寿司"), Language::Unknown);
+        assert_eq!(detect("12345 !?"), Language::Unknown);
+    }
+}
@@ -1 +1,3 @@
 pub mod baidu_translation;
+pub mod glossary;
+pub mod language;
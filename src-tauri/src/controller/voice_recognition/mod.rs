@@ -1,2 +1,6 @@
+pub mod openai;
 pub mod whisper_lib;
 pub mod whisper;
+pub mod whisper_models;
+pub mod vosk_lib;
+pub mod vosk;
@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+use reqwest::multipart::{Form, Part};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::config::voice_recognition::{RecognizeByOpenAi, TranscriptionResponseFormat};
+use crate::controller::errors::{CommonError, ProgramError};
+use crate::controller::recognizer::{RecognizedSegment, SpeechRecognizer};
+use crate::utils::audio;
+
+const TRANSCRIPTION_SAMPLE_RATE: u32 = 16000;
+
+#[derive(Debug, Deserialize)]
+struct TranscriptionSegment {
+    text: String,
+    // seconds from the start of audio
+    #[serde(default)]
+    start: f64,
+    #[serde(default)]
+    end: f64,
+}
+
+/// body of json and verbose_json response, verbose_json carries segments besides the full text
+#[derive(Debug, Deserialize)]
+struct TranscriptionResponse {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    segments: Vec<TranscriptionSegment>,
+}
+
+fn transcription_url(api_addr: &str) -> String {
+    let api_addr = api_addr.trim_end_matches('/');
+    if api_addr.ends_with("/v1") {
+        format!("{}/audio/transcriptions", api_addr)
+    } else {
+        format!("{}/v1/audio/transcriptions", api_addr)
+    }
+}
+
+fn response_format_name(format: &TranscriptionResponseFormat) -> &'static str {
+    match format {
+        TranscriptionResponseFormat::Json => "json",
+        TranscriptionResponseFormat::VerboseJson => "verbose_json",
+        TranscriptionResponseFormat::Text => "text",
+    }
+}
+
+fn parse_response(format: &TranscriptionResponseFormat, body: &str) -> Result<String, ProgramError> {
+    match format {
+        TranscriptionResponseFormat::Text => Ok(body.trim().to_string()),
+        TranscriptionResponseFormat::Json | TranscriptionResponseFormat::VerboseJson => {
+            let response: TranscriptionResponse = serde_json::from_str(body)?;
+            // some servers leave text empty in verbose_json and only fill segments
+            if response.text.trim().is_empty() && !response.segments.is_empty() {
+                let text = response.segments.iter()
+                    .map(|segment| segment.text.trim())
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+                return Ok(text);
+            }
+            Ok(response.text.trim().to_string())
+        }
+    }
+}
+
+/// timed segments of verbose_json response, the whole text as one segment if the server returns no segments
+fn parse_segments(body: &str, duration_ms: i64) -> Result<Vec<RecognizedSegment>, ProgramError> {
+    let response: TranscriptionResponse = serde_json::from_str(body)?;
+    if response.segments.is_empty() {
+        return Ok(vec![RecognizedSegment {
+            text: response.text.trim().to_string(),
+            start_ms: 0,
+            end_ms: duration_ms,
+        }]);
+    }
+    Ok(response.segments.into_iter()
+        .map(|segment| RecognizedSegment {
+            text: segment.text.trim().to_string(),
+            start_ms: (segment.start * 1000.0).round() as i64,
+            end_ms: (segment.end * 1000.0).round() as i64,
+        })
+        .collect())
+}
+
+async fn transcribe(config: &RecognizeByOpenAi,
+                    samples: &Vec<f32>,
+                    response_format: &TranscriptionResponseFormat) -> Result<String, ProgramError> {
+    let data = audio::encode_wav(samples, TRANSCRIPTION_SAMPLE_RATE)?;
+
+    let mut form = Form::new()
+        .part("file", Part::bytes(data).file_name("audio.wav").mime_str("audio/wav")?)
+        .text("model", config.model.clone())
+        .text("temperature", config.temperature.to_string())
+        .text("response_format", response_format_name(response_format));
+    if let Some(language) = config.language.clone().filter(|lang| !lang.is_empty()) {
+        form = form.text("language", language);
+    }
+    if let Some(prompt) = config.prompt.clone().filter(|prompt| !prompt.is_empty()) {
+        form = form.text("prompt", prompt);
+    }
+
+    let client = reqwest::Client::new();
+    let mut request = client
+        .post(transcription_url(&config.api_addr))
+        .multipart(form);
+    if let Some(api_key) = config.api_key.clone().filter(|key| !key.is_empty()) {
+        request = request.bearer_auth(api_key);
+    }
+    let res = request.send().await?;
+    if res.status() == StatusCode::OK {
+        Ok(res.text().await?)
+    } else {
+        Err(ProgramError::from(CommonError::from_http_error(res.status(), res.text().await?)))
+    }
+}
+
+/// recognize by an openai compatible transcription api
+pub struct OpenAiRecognizer {
+    config: RecognizeByOpenAi,
+}
+
+impl OpenAiRecognizer {
+    pub fn new(config: RecognizeByOpenAi) -> Self {
+        OpenAiRecognizer { config }
+    }
+}
+
+#[async_trait]
+impl SpeechRecognizer for OpenAiRecognizer {
+    fn name(&self) -> &'static str {
+        "OpenAi"
+    }
+
+    fn sample_rate(&self) -> u32 {
+        TRANSCRIPTION_SAMPLE_RATE
+    }
+
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    fn supported_languages(&self) -> Vec<String> {
+        // depends on the model served by the endpoint
+        vec![]
+    }
+
+    async fn recognize(&self, samples: &Vec<f32>) -> Result<String, ProgramError> {
+        let body = transcribe(&self.config, samples, &self.config.response_format).await?;
+        parse_response(&self.config.response_format, &body)
+    }
+
+    async fn recognize_segments(&self, samples: &Vec<f32>) -> Result<Vec<RecognizedSegment>, ProgramError> {
+        // only verbose_json carries timestamps of segments
+        let body = transcribe(&self.config, samples, &TranscriptionResponseFormat::VerboseJson).await?;
+        let duration_ms = samples.len() as i64 * 1000 / TRANSCRIPTION_SAMPLE_RATE as i64;
+        parse_segments(&body, duration_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcription_url() {
+        assert_eq!(transcription_url("http://127.0.0.1:8000"), "http://127.0.0.1:8000/v1/audio/transcriptions");
+        assert_eq!(transcription_url("https://api.openai.com/v1/"), "https://api.openai.com/v1/audio/transcriptions");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let json = r#"{"text": " こんにちは "}"#;
+        assert_eq!(parse_response(&TranscriptionResponseFormat::Json, json).unwrap(), "こんにちは");
+
+        let verbose = r#"{"task": "transcribe", "language": "japanese", "duration": 2.1, "text": "",
+            "segments": [{"id": 0, "start": 0.0, "end": 1.0, "text": " hello"},
+                         {"id": 1, "start": 1.0, "end": 2.1, "text": " world "}]}"#;
+        assert_eq!(parse_response(&TranscriptionResponseFormat::VerboseJson, verbose).unwrap(), "hello world");
+
+        assert_eq!(parse_response(&TranscriptionResponseFormat::Text, "hi\n").unwrap(), "hi");
+        assert!(parse_response(&TranscriptionResponseFormat::Json, "not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_segments() {
+        let verbose = r#"{"text": "hello world",
+            "segments": [{"id": 0, "start": 0.0, "end": 1.0, "text": " hello"},
+                         {"id": 1, "start": 1.0, "end": 2.1, "text": " world "}]}"#;
+        let segments = parse_segments(verbose, 3000).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!((segments[1].text.as_str(), segments[1].start_ms, segments[1].end_ms), ("world", 1000, 2100));
+
+        // servers without segments give the whole text spanning all audio
+        let segments = parse_segments(r#"{"text": " hi "}"#, 3000).unwrap();
+        assert_eq!((segments[0].text.as_str(), segments[0].start_ms, segments[0].end_ms), ("hi", 0, 3000));
+    }
+}
@@ -0,0 +1,86 @@
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use tauri::regex::Regex;
+
+use crate::common::{app, constants};
+use crate::config::voice_recognition::{RecognitionTool, RecognizeByVosk, VoiceRecognitionConfig};
+use crate::controller::errors::ProgramError;
+use crate::controller::recognizer::{RecognizedSegment, SpeechRecognizer};
+use crate::controller::voice_recognition::vosk_lib;
+pub use crate::controller::voice_recognition::vosk_lib::available_models;
+
+lazy_static! {
+    // official models are named like vosk-model-small-ja-0.22 or vosk-model-en-us-0.22
+    static ref MODEL_LANGUAGE: Regex = Regex::new(r"vosk-model-(?:small-)?([a-z]{2,3})(?:-|$)").unwrap();
+}
+
+/// recognize by vosk library, partial results are sent to frontend while decoding
+pub struct VoskRecognizer {
+    config: RecognizeByVosk,
+}
+
+impl VoskRecognizer {
+    pub fn new(config: RecognizeByVosk) -> Self {
+        VoskRecognizer { config }
+    }
+}
+
+#[async_trait]
+impl SpeechRecognizer for VoskRecognizer {
+    fn name(&self) -> &'static str {
+        "Vosk"
+    }
+
+    fn sample_rate(&self) -> u32 {
+        vosk_lib::VOSK_SAMPLE_RATE
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn supported_languages(&self) -> Vec<String> {
+        // every vosk model supports a single language
+        match MODEL_LANGUAGE.captures(&self.config.model) {
+            Some(captures) => vec![captures[1].to_string()],
+            None => vec![]
+        }
+    }
+
+    async fn recognize(&self, samples: &Vec<f32>) -> Result<String, ProgramError> {
+        log::debug!("Do asr by vosk library");
+        // show partial text in the recording popup like streaming whisper does
+        let on_partial = |partial: &str| {
+            app::silent_emit_all(constants::event::ON_RECORDING_RECOGNIZE_TEXT, partial.to_string());
+        };
+        let segments = vosk_lib::recognize(&self.config.model, self.config.library_path.as_deref(),
+                                           samples, on_partial).await?;
+        Ok(vosk_lib::segments_text(&segments))
+    }
+
+    async fn recognize_segments(&self, samples: &Vec<f32>) -> Result<Vec<RecognizedSegment>, ProgramError> {
+        // partial text is only meaningful for live recording
+        vosk_lib::recognize(&self.config.model, self.config.library_path.as_deref(),
+                            samples, |_: &str| {}).await
+    }
+}
+
+fn get_vosk_model(config: &VoiceRecognitionConfig) -> Option<String> {
+    if config.enable {
+        if let RecognitionTool::Vosk(vosk_config) = config.tool.clone() {
+            return Some(vosk_config.model);
+        }
+    }
+    None
+}
+
+/// free loaded vosk model if vosk is not used anymore or another model is chosen,
+/// the new model is loaded on next recognition
+pub async fn update_model(old: &VoiceRecognitionConfig, current: &VoiceRecognitionConfig) {
+    let old_model = get_vosk_model(old);
+    if old_model.is_some() && old_model != get_vosk_model(current) {
+        if let Err(err) = vosk_lib::free_model().await {
+            log::error!("Failed to free vosk model, err: {}", err);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::ffi::{CStr, CString};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use tokio::sync::Mutex;
+
+use crate::controller::errors::ProgramError;
+use crate::controller::recognizer::RecognizedSegment;
+
+pub const MODEL_PATH: &str = "vosk/models";
+
+const LIBRARY_DIR: &str = "vosk";
+#[cfg(target_os = "windows")]
+const LIBRARY_FILE_NAME: &str = "libvosk.dll";
+#[cfg(target_os = "macos")]
+const LIBRARY_FILE_NAME: &str = "libvosk.dylib";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARY_FILE_NAME: &str = "libvosk.so";
+
+pub const VOSK_SAMPLE_RATE: u32 = 16000;
+
+// feed audio to vosk by chunks of 200ms, so that partial results come out while decoding
+const CHUNK_SAMPLES: usize = 3200;
+
+lazy_static! {
+    static ref VOSK_LIB: Arc<Mutex<VoskLibrary>> = Arc::new(Mutex::new(VoskLibrary::new()));
+}
+
+// declaration of vosk structs
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VoskModel {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VoskRecognizer {
+    _unused: [u8; 0],
+}
+// ^^^^^^ declaration of vosk structs end
+
+/// vosk library wrapper, unlike whisper the library is loaded on first use,
+/// since most users won't have vosk installed at all
+pub struct VoskLibrary {
+    inner: Option<(PathBuf, libloading::Library)>,
+    model: Option<(PathBuf, *mut VoskModel)>,
+}
+
+unsafe impl Send for VoskLibrary {}
+
+unsafe impl Sync for VoskLibrary {}
+
+impl VoskLibrary {
+    fn new() -> Self {
+        VoskLibrary {
+            inner: None,
+            model: None,
+        }
+    }
+
+    /// load library of given file, a loaded library of another file is unloaded along with its model
+    fn ensure_library(&mut self, file: PathBuf) -> Result<(), ProgramError> {
+        if let Some((current, _)) = &self.inner {
+            if *current == file {
+                return Ok(());
+            }
+            self.vosk_model_free()?;
+            self.inner.take();
+        }
+        let lib = unsafe {
+            libloading::Library::new(&file)?
+        };
+        // kaldi logs are too verbose, disable them
+        let set_log_level: libloading::Symbol<unsafe extern "C" fn(std::os::raw::c_int)> = unsafe {
+            lib.get(b"vosk_set_log_level\0")?
+        };
+        unsafe {
+            set_log_level(-1);
+        }
+        self.inner.replace((file, lib));
+        Ok(())
+    }
+
+    fn lib(&self) -> Result<&libloading::Library, ProgramError> {
+        Ok(&self.inner.as_ref().ok_or("Vosk library is not loaded")?.1)
+    }
+
+    fn get_model(&self) -> Result<*mut VoskModel, ProgramError> {
+        Ok(self.model.as_ref().ok_or("Vosk model is not loaded")?.1)
+    }
+
+    /// load model of given directory, current model is kept if it is the same one
+    fn vosk_model_new(&mut self, library_file: PathBuf, model_dir: PathBuf) -> Result<(), ProgramError> {
+        self.ensure_library(library_file)?;
+        if let Some((current, _)) = &self.model {
+            if *current == model_dir {
+                return Ok(());
+            }
+        }
+        self.vosk_model_free()?;
+
+        if !model_dir.is_dir() {
+            return Err(ProgramError::from(format!("Vosk model directory {} not found",
+                                                  model_dir.to_string_lossy())));
+        }
+        let model_new: libloading::Symbol<unsafe extern "C" fn(*const std::os::raw::c_char) -> *mut VoskModel> = unsafe {
+            self.lib()?.get(b"vosk_model_new\0")?
+        };
+        let model_ptr = CString::new(model_dir.to_string_lossy().as_bytes())
+            .map_err(|_| {
+                ProgramError::from("unable to convert String to CString")
+            })?;
+        let model = unsafe {
+            model_new(model_ptr.as_ptr())
+        };
+        if model.is_null() {
+            return Err(ProgramError::from(format!("Failed to load vosk model {}",
+                                                  model_dir.to_string_lossy())));
+        }
+        self.model.replace((model_dir, model));
+        log::debug!("Load vosk model success");
+        Ok(())
+    }
+
+    fn vosk_model_free(&mut self) -> Result<(), ProgramError> {
+        if self.model.is_none() {
+            return Ok(());
+        }
+        let free: libloading::Symbol<unsafe extern "C" fn(*mut VoskModel)> = unsafe {
+            self.lib()?.get(b"vosk_model_free\0")?
+        };
+        unsafe {
+            free(self.get_model()?);
+        }
+        self.model.take();
+        log::debug!("Free current vosk model success");
+        Ok(())
+    }
+
+    fn vosk_recognizer_new(&self, sample_rate: f32) -> Result<*mut VoskRecognizer, ProgramError> {
+        let recognizer_new: libloading::Symbol<unsafe extern "C" fn(*mut VoskModel, f32) -> *mut VoskRecognizer> = unsafe {
+            self.lib()?.get(b"vosk_recognizer_new\0")?
+        };
+        let recognizer = unsafe {
+            recognizer_new(self.get_model()?, sample_rate)
+        };
+        if recognizer.is_null() {
+            return Err(ProgramError::from("Failed to create vosk recognizer"));
+        }
+        Ok(recognizer)
+    }
+
+    fn vosk_recognizer_free(&self, recognizer: *mut VoskRecognizer) -> Result<(), ProgramError> {
+        let free: libloading::Symbol<unsafe extern "C" fn(*mut VoskRecognizer)> = unsafe {
+            self.lib()?.get(b"vosk_recognizer_free\0")?
+        };
+        unsafe {
+            free(recognizer);
+        }
+        Ok(())
+    }
+
+    /// returns 1 if an utterance is finished and its result can be read, 0 if decoding continues
+    fn vosk_recognizer_accept_waveform_f(&self,
+                                         recognizer: *mut VoskRecognizer,
+                                         data: &[f32]) -> Result<std::os::raw::c_int, ProgramError> {
+        let accept_waveform: libloading::Symbol<unsafe extern "C" fn(
+            *mut VoskRecognizer,
+            *const f32,
+            std::os::raw::c_int,
+        ) -> std::os::raw::c_int> = unsafe {
+            self.lib()?.get(b"vosk_recognizer_accept_waveform_f\0")?
+        };
+        let result = unsafe {
+            accept_waveform(recognizer, data.as_ptr(), data.len() as std::os::raw::c_int)
+        };
+        if result < 0 {
+            return Err(ProgramError::from(format!("vosk accept waveform failed, code {}", result)));
+        }
+        Ok(result)
+    }
+
+    /// call one of vosk_recognizer_result, vosk_recognizer_partial_result and
+    /// vosk_recognizer_final_result, which all return a json string owned by the recognizer
+    fn vosk_recognizer_json(&self,
+                            recognizer: *mut VoskRecognizer,
+                            symbol: &[u8]) -> Result<String, ProgramError> {
+        let get_result: libloading::Symbol<unsafe extern "C" fn(
+            *mut VoskRecognizer
+        ) -> *const std::os::raw::c_char> = unsafe {
+            self.lib()?.get(symbol)?
+        };
+        let json = unsafe {
+            CStr::from_ptr(get_result(recognizer)).to_string_lossy().to_string()
+        };
+        Ok(json)
+    }
+}
+
+/// extract field like "text" or "partial" from vosk result json
+fn parse_result(json: &str, key: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(json)
+        .ok()
+        .and_then(|value| value[key].as_str().map(|text| text.trim().to_string()))
+        .unwrap_or_default()
+}
+
+/// resolve library setting into a library file, `library_path` is either the library file
+/// or the directory containing it, the library in [LIBRARY_DIR] is used if it is not set
+pub fn library_file(library_path: Option<&str>) -> PathBuf {
+    match library_path.map(str::trim).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if path.is_dir() {
+                path.join(LIBRARY_FILE_NAME)
+            } else {
+                path
+            }
+        }
+        None => PathBuf::from(LIBRARY_DIR).join(LIBRARY_FILE_NAME)
+    }
+}
+
+/// resolve model setting into a model directory, relative names are looked up in [MODEL_PATH]
+pub fn model_dir(model: &str) -> PathBuf {
+    let path = PathBuf::from(model);
+    if path.is_absolute() {
+        path
+    } else {
+        PathBuf::from(MODEL_PATH).join(model)
+    }
+}
+
+fn samples_ms(samples: usize) -> i64 {
+    samples as i64 * 1000 / VOSK_SAMPLE_RATE as i64
+}
+
+/// decode data into one segment per utterance vosk finished, an utterance spans the chunks
+/// fed since the previous one finished, so its timestamps are accurate to a chunk
+fn decode(lib: &VoskLibrary,
+          recognizer: *mut VoskRecognizer,
+          data: &[f32],
+          on_partial: &mut (dyn FnMut(&str) + Send)) -> Result<Vec<RecognizedSegment>, ProgramError> {
+    let mut segments: Vec<RecognizedSegment> = vec![];
+    let mut utterance_start = 0;
+    let mut fed = 0;
+    let mut last_partial = String::new();
+    for chunk in data.chunks(CHUNK_SAMPLES) {
+        // vosk takes float samples in the range of 16 bit integers
+        let chunk: Vec<f32> = chunk.iter().map(|sample| sample * 32768.0).collect();
+        let finished = lib.vosk_recognizer_accept_waveform_f(recognizer, &chunk)?;
+        fed += chunk.len();
+        if finished == 1 {
+            let json = lib.vosk_recognizer_json(recognizer, b"vosk_recognizer_result\0")?;
+            segments.push(RecognizedSegment {
+                text: parse_result(&json, "text"),
+                start_ms: samples_ms(utterance_start),
+                end_ms: samples_ms(fed),
+            });
+            utterance_start = fed;
+            last_partial.clear();
+        } else {
+            let json = lib.vosk_recognizer_json(recognizer, b"vosk_recognizer_partial_result\0")?;
+            let partial = parse_result(&json, "partial");
+            if !partial.is_empty() && partial != last_partial {
+                on_partial(&partial);
+                last_partial = partial;
+            }
+        }
+    }
+    let json = lib.vosk_recognizer_json(recognizer, b"vosk_recognizer_final_result\0")?;
+    segments.push(RecognizedSegment {
+        text: parse_result(&json, "text"),
+        start_ms: samples_ms(utterance_start),
+        end_ms: samples_ms(fed),
+    });
+    Ok(segments.into_iter()
+        .filter(|segment| !segment.text.is_empty())
+        .collect())
+}
+
+/// text of all segments recognized by vosk
+pub fn segments_text(segments: &[RecognizedSegment]) -> String {
+    segments.iter()
+        .map(|segment| segment.text.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// recognize mono audio data of rate [VOSK_SAMPLE_RATE] with given model into utterance segments,
+/// partial results are reported by `on_partial` while decoding
+pub async fn recognize<F>(model: &str,
+                          library_path: Option<&str>,
+                          data: &Vec<f32>,
+                          mut on_partial: F) -> Result<Vec<RecognizedSegment>, ProgramError>
+    where F: FnMut(&str) + Send + 'static {
+    let mut lib = VOSK_LIB.clone().lock_owned().await;
+    let library_file = library_file(library_path);
+    let model_dir = model_dir(model);
+    let data = data.clone();
+    // loading model and decoding block for seconds, keep them off the async workers
+    tauri::async_runtime::spawn_blocking(move || {
+        lib.vosk_model_new(library_file, model_dir)?;
+        let recognizer = lib.vosk_recognizer_new(VOSK_SAMPLE_RATE as f32)?;
+        let result = decode(&lib, recognizer, &data, &mut on_partial);
+        lib.vosk_recognizer_free(recognizer)?;
+        result
+    }).await?
+}
+
+/// free vosk model
+pub async fn free_model() -> Result<(), ProgramError> {
+    let lock = VOSK_LIB.clone();
+    let mut lib = lock.lock().await;
+    lib.vosk_model_free()
+}
+
+/// list all model directories in [MODEL_PATH]
+pub fn available_models() -> Result<Vec<String>, ProgramError> {
+    let model_path = PathBuf::from(MODEL_PATH);
+    let mut models = vec![];
+    if !model_path.is_dir() {
+        return Ok(models);
+    }
+    for entry in std::fs::read_dir(model_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            models.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    models.sort();
+    Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_result() {
+        assert_eq!(parse_result(r#"{"partial" : "こんにちは 世界"}"#, "partial"), "こんにちは 世界");
+        assert_eq!(parse_result("{\n  \"text\" : \"\"\n}", "text"), "");
+        assert_eq!(parse_result("broken", "text"), "");
+    }
+
+    #[test]
+    fn test_library_file() {
+        assert_eq!(library_file(None), PathBuf::from(LIBRARY_DIR).join(LIBRARY_FILE_NAME));
+        assert_eq!(library_file(Some(" ")), PathBuf::from(LIBRARY_DIR).join(LIBRARY_FILE_NAME));
+        let dir = std::env::temp_dir();
+        assert_eq!(library_file(dir.to_str()), dir.join(LIBRARY_FILE_NAME));
+        assert_eq!(library_file(Some("lib/custom-vosk.so")), PathBuf::from("lib/custom-vosk.so"));
+    }
+
+    #[test]
+    fn test_segments_text() {
+        assert_eq!(samples_ms(CHUNK_SAMPLES), 200);
+        let segments = vec![
+            RecognizedSegment { text: "こんにちは".to_string(), start_ms: 0, end_ms: 1200 },
+            RecognizedSegment { text: "世界".to_string(), start_ms: 1200, end_ms: 2000 },
+        ];
+        assert_eq!(segments_text(&segments), "こんにちは 世界");
+    }
+}
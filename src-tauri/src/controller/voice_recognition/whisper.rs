@@ -1,39 +1,74 @@
-use reqwest::header::HeaderMap;
+use async_trait::async_trait;
 use reqwest::multipart::{Form, Part};
 use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::sync::mpsc;
 
-use crate::config::voice_recognition::{RecognitionTool, RecognizeByWhisper, VoiceRecognitionConfig, WhisperConfigType};
+use crate::common::{app, constants};
+use crate::config::voice_recognition::{RecognitionTool, RecognizeByWhisper, VoiceRecognitionConfig, WhisperConfigType, WhisperTask};
 use crate::controller::errors::{CommonError, ProgramError};
+use crate::controller::recognizer::{RecognizedSegment, SpeechRecognizer};
 use crate::controller::voice_recognition::whisper_lib;
 pub use crate::controller::voice_recognition::whisper_lib::available_models;
 pub use crate::controller::voice_recognition::whisper_lib::init_library as check_whisper_lib;
+pub use crate::controller::voice_recognition::whisper_lib::library_status;
 use crate::utils::audio;
 
-const REQ_TASK: &str = "transcribe";
-const REQ_OUTPUT: &str = "txt";
+// plain text for live recognition, json carrying timed segments for transcription of files
+const REQ_OUTPUT_TEXT: &str = "txt";
+const REQ_OUTPUT_JSON: &str = "json";
 
-async fn asr_by_http(config: &RecognizeByWhisper, samples: &Vec<f32>) -> Result<String, ProgramError> {
-    let client = reqwest::Client::new();
+#[derive(Debug, Deserialize)]
+struct AsrSegment {
+    text: String,
+    // seconds from the start of audio
+    start: f64,
+    end: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AsrJsonResponse {
+    #[serde(default)]
+    segments: Vec<AsrSegment>,
+}
 
-    let mut headers = HeaderMap::new();
+fn parse_asr_segments(body: &str) -> Result<Vec<RecognizedSegment>, ProgramError> {
+    let response: AsrJsonResponse = serde_json::from_str(body)?;
+    Ok(response.segments.into_iter()
+        .map(|segment| RecognizedSegment {
+            text: segment.text.trim().to_string(),
+            start_ms: (segment.start * 1000.0).round() as i64,
+            end_ms: (segment.end * 1000.0).round() as i64,
+        })
+        .filter(|segment| !segment.text.is_empty())
+        .collect())
+}
 
-    headers.insert("Content-Type", "multipart/form-data".parse().unwrap());
-    headers.insert("Accept", "application/json".parse().unwrap());
+async fn asr_by_http(config: &RecognizeByWhisper, samples: &Vec<f32>, output: &str) -> Result<String, ProgramError> {
+    let client = reqwest::Client::new();
 
-    let data = unsafe {
-        std::slice::from_raw_parts(samples.as_ptr() as *const _, samples.len() * 4)
-    };
+    // whisper asr webservice decodes the upload by ffmpeg, so send a real wav file
+    let data = audio::encode_wav(samples, WHISPER_SAMPLE_RATE)?;
 
     let form = Form::new()
-        .part("audio_file", Part::bytes(data).file_name("asr.wav"));
+        .part("audio_file", Part::bytes(data).file_name("asr.wav").mime_str("audio/wav")?);
 
-    let mut query = vec![("task", REQ_TASK), ("output", REQ_OUTPUT)];
+    let task = match config.task {
+        WhisperTask::Transcribe => "transcribe",
+        WhisperTask::Translate => "translate",
+    };
+    let mut query = vec![("task", task), ("output", output), ("encode", "true")];
     let language;
     if config.language.is_some() {
         language = config.language.clone().unwrap();
         query.push(("language", &*language))
     }
 
+    let prompt = config.prompt();
+    if let Some(prompt) = &prompt {
+        query.push(("initial_prompt", prompt))
+    }
+
     let query = query;
     let res: reqwest::Response = client
         .post(format!("{}/asr", config.api_addr))
@@ -49,70 +84,136 @@ async fn asr_by_http(config: &RecognizeByWhisper, samples: &Vec<f32>) -> Result<
     }
 }
 
-async fn asr_by_lib(config: &RecognizeByWhisper, samples: &Vec<f32>) -> Result<String, ProgramError> {
-    log::debug!("Do ast by whisper library");
-    whisper_lib::recognize(config.language.clone(), samples).await
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+// languages supported by whisper models
+const WHISPER_LANGUAGES: [&str; 99] = [
+    "en", "zh", "de", "es", "ru", "ko", "fr", "ja", "pt", "tr", "pl", "ca", "nl", "ar", "sv",
+    "it", "id", "hi", "fi", "vi", "he", "uk", "el", "ms", "cs", "ro", "da", "hu", "ta", "no",
+    "th", "ur", "hr", "bg", "lt", "la", "mi", "ml", "cy", "sk", "te", "fa", "lv", "bn", "sr",
+    "az", "sl", "kn", "et", "mk", "br", "eu", "is", "hy", "ne", "mn", "bs", "kk", "sq", "sw",
+    "gl", "mr", "pa", "si", "km", "sn", "yo", "so", "af", "oc", "ka", "be", "tg", "sd", "gu",
+    "am", "yi", "lo", "uz", "fo", "ht", "ps", "tk", "nn", "mt", "sa", "lb", "my", "bo", "tl",
+    "mg", "as", "tt", "haw", "ln", "ha", "ba", "jw", "su",
+];
+
+fn whisper_languages(model: &str) -> Vec<String> {
+    // english only models like base.en
+    if model.ends_with(".en") {
+        return vec!["en".to_string()];
+    }
+    WHISPER_LANGUAGES.iter().map(|lang| lang.to_string()).collect()
 }
 
-async fn asr_16k_mono(config: &RecognizeByWhisper,
-                      mono_16k_samples: &Vec<f32>) -> Result<String, ProgramError> {
-    match config.config_type {
-        WhisperConfigType::Http => {
-            asr_by_http(config, mono_16k_samples).await
-        }
-        WhisperConfigType::Binary => {
-            asr_by_lib(config, mono_16k_samples).await
-        }
+/// recognize by a remote whisper asr webservice
+pub struct WhisperHttpRecognizer {
+    config: RecognizeByWhisper,
+}
+
+impl WhisperHttpRecognizer {
+    pub fn new(config: RecognizeByWhisper) -> Self {
+        WhisperHttpRecognizer { config }
     }
 }
 
-async fn asr_mono(config: &RecognizeByWhisper,
-                  mono_samples: &Vec<f32>,
-                  sample_rate: u32) -> Result<String, ProgramError> {
-    if sample_rate != 16000 {
-        log::debug!("Convert audio to rate 16k");
-        let sample_16k = samplerate::convert(sample_rate,
-                                             16000,
-                                             1,
-                                             samplerate::ConverterType::SincBestQuality,
-                                             &mono_samples)
-            .map_err(|err| ProgramError::from(err))?;
-        asr_16k_mono(config, &sample_16k).await
-    } else {
-        asr_16k_mono(config, mono_samples).await
+#[async_trait]
+impl SpeechRecognizer for WhisperHttpRecognizer {
+    fn name(&self) -> &'static str {
+        "WhisperHttp"
+    }
+
+    fn sample_rate(&self) -> u32 {
+        WHISPER_SAMPLE_RATE
+    }
+
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    fn supported_languages(&self) -> Vec<String> {
+        // model is chosen by the remote service
+        WHISPER_LANGUAGES.iter().map(|lang| lang.to_string()).collect()
+    }
+
+    async fn recognize(&self, samples: &Vec<f32>) -> Result<String, ProgramError> {
+        asr_by_http(&self.config, samples, REQ_OUTPUT_TEXT).await
+    }
+
+    async fn recognize_segments(&self, samples: &Vec<f32>) -> Result<Vec<RecognizedSegment>, ProgramError> {
+        let body = asr_by_http(&self.config, samples, REQ_OUTPUT_JSON).await?;
+        parse_asr_segments(&body)
     }
 }
 
-async fn asr_non_mono(config: &RecognizeByWhisper,
-                      samples: &Vec<f32>,
-                      channels: u16,
-                      sample_rate: u32) -> Result<String, ProgramError> {
-    assert!(channels > 1, "None mono channels should be greater than 1");
-    let mono_samples = audio::convert_to_mono(&samples, channels);
-    asr_mono(config, &mono_samples, sample_rate).await
+/// recognize by whisper.cpp library loaded locally
+pub struct WhisperLibRecognizer {
+    config: RecognizeByWhisper,
 }
 
-pub async fn asr(config: &RecognizeByWhisper,
-                 data: &Vec<f32>,
-                 channels: u16,
-                 sample_rate: u32) -> Result<String, ProgramError> {
-    if channels <= 0 {
-        return Err(ProgramError::from(format!("unsupported input channel value: {}", channels)));
+impl WhisperLibRecognizer {
+    pub fn new(config: RecognizeByWhisper) -> Self {
+        WhisperLibRecognizer { config }
     }
-    if channels == 1 {
-        asr_mono(config, data, sample_rate).await
-    } else {
-        asr_non_mono(config, data, channels, sample_rate).await
+}
+
+#[async_trait]
+impl SpeechRecognizer for WhisperLibRecognizer {
+    fn name(&self) -> &'static str {
+        "WhisperLib"
+    }
+
+    fn sample_rate(&self) -> u32 {
+        WHISPER_SAMPLE_RATE
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn supported_languages(&self) -> Vec<String> {
+        whisper_languages(&self.config.use_model)
+    }
+
+    async fn recognize(&self, samples: &Vec<f32>) -> Result<String, ProgramError> {
+        log::debug!("Do asr by whisper library");
+        // show text of segments as soon as whisper decodes them
+        let (segment_tx, mut segment_rx) = mpsc::unbounded_channel::<whisper_lib::Segment>();
+        let emitter = tauri::async_runtime::spawn(async move {
+            let mut text = String::new();
+            while let Some(segment) = segment_rx.recv().await {
+                if segment.low_confidence {
+                    continue;
+                }
+                text.push_str(&segment.text);
+                app::silent_emit_all(constants::event::ON_RECORDING_RECOGNIZE_TEXT,
+                                     text.trim().to_string());
+            }
+        });
+        let segments = whisper_lib::recognize_segments(&self.config, samples, Some(segment_tx)).await;
+        let _ = emitter.await;
+        Ok(whisper_lib::segments_text(&segments?))
+    }
+
+    async fn recognize_segments(&self, samples: &Vec<f32>) -> Result<Vec<RecognizedSegment>, ProgramError> {
+        let config = whisper_lib::file_transcription_config(&self.config);
+        let segments = whisper_lib::recognize_segments(&config, samples, None).await?;
+        Ok(segments.into_iter()
+            .filter(|segment| !segment.low_confidence)
+            .map(|segment| RecognizedSegment {
+                text: segment.text,
+                start_ms: segment.start_ms,
+                end_ms: segment.end_ms,
+            })
+            .collect())
     }
 }
 
-fn get_whisper_lib_model(config: &VoiceRecognitionConfig) -> Option<String> {
+/// model and library path of whisper binary config
+fn get_whisper_lib_model(config: &VoiceRecognitionConfig) -> Option<(String, Option<String>)> {
     if config.enable {
-        match config.tool.clone() {
-            RecognitionTool::Whisper(whisper_config) => {
-                if whisper_config.config_type == WhisperConfigType::Binary {
-                    return Some(whisper_config.use_model);
-                }
+        if let RecognitionTool::Whisper(whisper_config) = config.tool.clone() {
+            if whisper_config.config_type == WhisperConfigType::Binary {
+                return Some((whisper_config.use_model, whisper_config.library_path));
             }
         }
     }
@@ -131,8 +232,8 @@ pub async fn update_model(old: &VoiceRecognitionConfig, current: &VoiceRecogniti
         }
     }
     if current_model.is_some() && old_model != current_model {
-        let update_model = current_model.unwrap();
-        match whisper_lib::update_model(update_model.clone()).await {
+        let (update_model, library_path) = current_model.unwrap();
+        match whisper_lib::update_model(update_model.clone(), library_path).await {
             Ok(_) => {
                 return Some(update_model);
             }
@@ -142,4 +243,104 @@ pub async fn update_model(old: &VoiceRecognitionConfig, current: &VoiceRecogniti
         }
     }
     None
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    use crate::config::voice_recognition::WhisperDecodeParams;
+
+    use super::*;
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    /// accept one request, send back its raw bytes and answer with given text
+    fn stub_server(answer: &'static str) -> (String, mpsc::Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                if let Some(header_end) = find(&request, b"\r\n\r\n") {
+                    let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+                    let content_length = headers.lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map(|len| len.trim().parse::<usize>().unwrap())
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", answer.len(), answer);
+            stream.write_all(response.as_bytes()).unwrap();
+            tx.send(request).unwrap();
+        });
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_asr_by_http_uploads_wav() {
+        let (addr, rx) = stub_server("hello");
+        let config = RecognizeByWhisper {
+            config_type: WhisperConfigType::Http,
+            use_model: "base".to_string(),
+            api_addr: addr,
+            language: Some("ja".to_string()),
+            decode: WhisperDecodeParams::default(),
+            initial_prompt: Some("Hello.".to_string()),
+            vocabulary: vec!["AIVoiceFactory".to_string()],
+            min_confidence: 0.0,
+            task: WhisperTask::Transcribe,
+            skip_translation: false,
+            library_path: None,
+            state_pool_size: 1,
+        };
+        let samples: Vec<f32> = (0..16000).map(|i| (i as f32 / 100.0).sin() * 0.5).collect();
+
+        let text = asr_by_http(&config, &samples, REQ_OUTPUT_TEXT).await.unwrap();
+        assert_eq!(text, "hello");
+
+        let request = rx.recv().unwrap();
+        let request_line = String::from_utf8_lossy(&request[..find(&request, b"\r\n").unwrap()]).to_string();
+        assert!(request_line.starts_with("POST /asr?task=transcribe&output=txt&encode=true&language=ja&initial_prompt=Hello.+AIVoiceFactory "));
+        assert!(find(&request, b"filename=\"asr.wav\"").is_some());
+        assert!(find(&request, b"Content-Type: audio/wav").is_some());
+
+        // the uploaded file is a complete 16k mono wav
+        let riff = find(&request, b"RIFF").unwrap();
+        let riff_size = u32::from_le_bytes(request[riff + 4..riff + 8].try_into().unwrap()) as usize;
+        let wav = &request[riff..riff + 8 + riff_size];
+        assert_eq!(&wav[8..12], b"WAVE");
+        let reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.spec().sample_rate, 16000);
+        assert_eq!(reader.len() as usize, samples.len());
+    }
+
+    #[test]
+    fn test_parse_asr_segments() {
+        let body = r#"{"text": " hello world", "language": "en",
+            "segments": [{"id": 0, "seek": 0, "start": 0.0, "end": 1.5, "text": " hello"},
+                         {"id": 1, "seek": 0, "start": 1.5, "end": 2.0, "text": " "},
+                         {"id": 2, "seek": 0, "start": 2.0, "end": 3.25, "text": " world"}]}"#;
+        let segments = parse_asr_segments(body).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!((segments[0].text.as_str(), segments[0].start_ms, segments[0].end_ms), ("hello", 0, 1500));
+        assert_eq!((segments[1].text.as_str(), segments[1].start_ms, segments[1].end_ms), ("world", 2000, 3250));
+        assert!(parse_asr_segments("hello").is_err());
+    }
+}
@@ -3,32 +3,72 @@
 #![allow(non_upper_case_globals)]
 
 use std::ffi::{CStr, CString};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use lazy_static::lazy_static;
-use tauri::regex;
-use tauri::regex::Regex;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
 use tokio::sync::broadcast::{Receiver, Sender};
+use tokio::sync::mpsc::UnboundedSender;
 
+use crate::common::{app, constants};
 use crate::config::voice_recognition;
-use crate::config::voice_recognition::WhisperConfigType;
+use crate::config::voice_recognition::{RecognizeByWhisper, WhisperConfigType, WhisperDecodeParams, WhisperSamplingStrategy, WhisperTask};
 use crate::controller::errors::ProgramError;
+use crate::controller::voice_recognition::whisper_models;
 use crate::utils;
-use crate::utils::http;
 
-const MODEL_PATH: &str = "whisper/models";
-const MODEL_TMP_FILE: &str = "model.tmp";
+const LIBRARY_DIR: &str = "whisper";
+#[cfg(target_os = "windows")]
+const LIBRARY_FILE_NAME: &str = "whisper.dll";
+#[cfg(target_os = "macos")]
+const LIBRARY_FILE_NAME: &str = "libwhisper.dylib";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARY_FILE_NAME: &str = "libwhisper.so";
 
-const DLL_FILE: &str = "whisper/whisper.dll";
-const DLL_DOWNLOAD_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/";
+// every symbol bound below, a library missing any of them can't be used
+const REQUIRED_SYMBOLS: [&str; 13] = [
+    "whisper_init_from_file",
+    "whisper_free",
+    "whisper_full_default_params",
+    "whisper_full",
+    "whisper_tokenize",
+    "whisper_token_eot",
+    "whisper_full_n_segments",
+    "whisper_full_get_segment_text",
+    "whisper_full_get_segment_t0",
+    "whisper_full_get_segment_t1",
+    "whisper_full_n_tokens",
+    "whisper_full_get_token_data",
+    "whisper_print_system_info",
+];
+// state api introduced by whisper.cpp v1.3.0, with which recognitions run concurrently on their own states;
+// `whisper_full_params` of v1.3.x only differs from v1.2.x on signatures of callbacks
+const STATE_SYMBOLS: [&str; 10] = [
+    "whisper_init_from_file_no_state",
+    "whisper_init_state",
+    "whisper_free_state",
+    "whisper_full_with_state",
+    "whisper_full_n_segments_from_state",
+    "whisper_full_get_segment_text_from_state",
+    "whisper_full_get_segment_t0_from_state",
+    "whisper_full_get_segment_t1_from_state",
+    "whisper_full_n_tokens_from_state",
+    "whisper_full_get_token_data_from_state",
+];
+// symbols introduced by later versions, which changed layout of `whisper_full_params`
+const UNSUPPORTED_SYMBOLS: [&str; 2] = ["whisper_full_default_params_by_ref", "whisper_context_default_params"];
+
+// half of the text context size(448) of whisper models
+const MAX_PROMPT_TOKENS: usize = 224;
 
 lazy_static! {
-    static ref WHISPER_LIB: Arc<Mutex<WhisperLibrary>> = Arc::new(Mutex::new(WhisperLibrary::new()));
+    // recognitions hold read lock while loading and freeing model hold write lock
+    static ref WHISPER_LIB: Arc<RwLock<WhisperLibrary>> = Arc::new(RwLock::new(WhisperLibrary::new()));
     static ref MODEL_LOAD_STOP_SIG: (Sender<()>, Receiver<()>) = broadcast::channel(1);
     static ref MODEL_AVAILABLE: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    static ref LIBRARY_STATUS: RwLock<WhisperLibraryStatus> = RwLock::new(WhisperLibraryStatus::default());
 }
 
 // declaration of whisper structs and constants
@@ -38,6 +78,12 @@ pub struct whisper_context {
     _unused: [u8; 0],
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct whisper_state {
+    _unused: [u8; 0],
+}
+
 pub type whisper_sampling_strategy = std::os::raw::c_int;
 pub type whisper_token = std::os::raw::c_int;
 
@@ -62,6 +108,16 @@ pub type whisper_new_segment_callback = Option<
     ),
 >;
 
+// signature of new segment callback since v1.3.0
+pub type whisper_new_segment_callback_with_state = Option<
+    unsafe extern "C" fn(
+        ctx: *mut whisper_context,
+        state: *mut whisper_state,
+        n_new: std::os::raw::c_int,
+        user_data: *mut std::os::raw::c_void,
+    ),
+>;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct whisper_token_data {
@@ -138,10 +194,112 @@ pub const whisper_sampling_strategy_WHISPER_SAMPLING_GREEDY: whisper_sampling_st
 pub const whisper_sampling_strategy_WHISPER_SAMPLING_BEAM_SEARCH: whisper_sampling_strategy = 1;
 // ^^^^^^ declaration of whisper structs and constants end
 
-/// whisper library wrapper
+/// load state of whisper library reported to frontend
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct WhisperLibraryStatus {
+    pub available: bool,
+    pub path: String,
+    // output of `whisper_print_system_info`
+    pub system_info: Option<String>,
+    // whether recognitions run concurrently, which requires state api of whisper.cpp v1.3
+    pub concurrent: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WhisperAbi {
+    V1_2,
+    // with state api
+    V1_3,
+}
+
+struct StatePoolInner {
+    idle: Vec<*mut whisper_state>,
+    created: usize,
+}
+
+// states are only touched by the recognition holding it
+unsafe impl Send for StatePoolInner {}
+
+/// states of loaded model, a state holds the decoding buffers and results of one recognition,
+/// states are created on demand until pool size is reached and reused after
+struct StatePool {
+    inner: parking_lot::Mutex<StatePoolInner>,
+    released: Notify,
+}
+
+/// a state taken from pool, which is put back on drop
+struct PooledState<'a> {
+    pool: &'a StatePool,
+    state: *mut whisper_state,
+}
+
+impl Drop for PooledState<'_> {
+    fn drop(&mut self) {
+        self.pool.inner.lock().idle.push(self.state);
+        self.pool.released.notify_one();
+    }
+}
+
+impl StatePool {
+    fn new() -> Self {
+        StatePool {
+            inner: parking_lot::Mutex::new(StatePoolInner { idle: vec![], created: 0 }),
+            released: Notify::new(),
+        }
+    }
+
+    /// take an idle state, or create one if less than `size` states are in use,
+    /// otherwise wait for a state to be released
+    async fn acquire<'a>(&'a self, lib: &WhisperLibrary, size: usize) -> Result<PooledState<'a>, ProgramError> {
+        let size = size.max(1);
+        loop {
+            let released = self.released.notified();
+            let create = {
+                let mut inner = self.inner.lock();
+                if inner.created - inner.idle.len() >= size {
+                    false
+                } else if let Some(state) = inner.idle.pop() {
+                    return Ok(PooledState { pool: self, state });
+                } else {
+                    inner.created += 1;
+                    true
+                }
+            };
+            if create {
+                return match lib.whisper_init_state() {
+                    Ok(state) => {
+                        log::debug!("Create whisper state for pool of size {}", size);
+                        Ok(PooledState { pool: self, state })
+                    }
+                    Err(err) => {
+                        self.inner.lock().created -= 1;
+                        Err(err)
+                    }
+                };
+            }
+            released.await;
+        }
+    }
+
+    /// take all states out to free them, must be called when no state is in use
+    fn take_all(&self) -> Vec<*mut whisper_state> {
+        let mut inner = self.inner.lock();
+        inner.created = 0;
+        std::mem::take(&mut inner.idle)
+    }
+}
+
+/// whisper library wrapper, the library is loaded on first use so a missing or
+/// incompatible library only makes whisper binary mode unavailable
 pub struct WhisperLibrary {
-    inner: libloading::Library,
+    inner: Option<libloading::Library>,
+    path: Option<PathBuf>,
+    abi: WhisperAbi,
     context: Option<*mut whisper_context>,
+    states: StatePool,
+    // recognitions are serialized on the default state of context if state api is unavailable
+    context_lock: Mutex<()>,
 }
 
 unsafe impl Send for WhisperLibrary {}
@@ -150,14 +308,47 @@ unsafe impl Sync for WhisperLibrary {}
 
 impl WhisperLibrary {
     fn new() -> Self {
-        let file = PathBuf::from(DLL_FILE);
-        let lib = unsafe {
-            libloading::Library::new(file).expect("Unable to load whisper.dll")
-        };
         WhisperLibrary {
-            inner: lib,
+            inner: None,
+            path: None,
+            abi: WhisperAbi::V1_2,
             context: None,
+            states: StatePool::new(),
+            context_lock: Mutex::new(()),
+        }
+    }
+
+    fn library(&self) -> Result<&libloading::Library, ProgramError> {
+        self.inner.as_ref()
+            .ok_or_else(|| ProgramError::from("Whisper library is not loaded"))
+    }
+
+    /// load library of given file, nothing is done if it's already loaded
+    fn load(&mut self, file: &Path) -> Result<(), ProgramError> {
+        if self.inner.is_some() && self.path.as_deref() == Some(file) {
+            return Ok(());
         }
+        // context belongs to the library it's created by
+        self.whisper_free()?;
+        self.inner = None;
+        self.path = Some(file.to_path_buf());
+
+        let lib = unsafe {
+            libloading::Library::new(file)?
+        };
+        self.abi = check_symbols(&lib)?;
+        self.inner = Some(lib);
+        Ok(())
+    }
+
+    fn whisper_print_system_info(&self) -> Result<String, ProgramError> {
+        let print_system_info: libloading::Symbol<unsafe extern "C" fn() -> *const std::os::raw::c_char> = unsafe {
+            self.library()?.get(b"whisper_print_system_info\0")?
+        };
+        let info = unsafe {
+            CStr::from_ptr(print_system_info()).to_string_lossy().to_string()
+        };
+        Ok(info)
     }
 
     fn get_context(&self) -> Result<*mut whisper_context, ProgramError> {
@@ -169,8 +360,13 @@ impl WhisperLibrary {
         if self.context.is_some() {
             self.whisper_free()?;
         }
+        // states are created by pool with state api, so the default state of context is not needed
+        let symbol: &[u8] = match self.abi {
+            WhisperAbi::V1_2 => b"whisper_init_from_file\0",
+            WhisperAbi::V1_3 => b"whisper_init_from_file_no_state\0",
+        };
         let init_from_file: libloading::Symbol<unsafe extern "C" fn(*const std::os::raw::c_char) -> *mut whisper_context> = unsafe {
-            self.inner.get(b"whisper_init_from_file\0")?
+            self.library()?.get(symbol)?
         };
         let model_ptr = CString::new(file.as_bytes())
             .map_err(|_| {
@@ -179,64 +375,156 @@ impl WhisperLibrary {
         let context = unsafe {
             init_from_file(model_ptr.as_ptr())
         };
+        if context.is_null() {
+            return Err(ProgramError::from(format!("Failed to init whisper context from file {}", file)));
+        }
         self.context.replace(context);
         MODEL_AVAILABLE.store(true, Ordering::Release);
         drop(model_ptr);
         Ok(())
     }
 
-    fn whisper_full_default_params(&self) -> Result<whisper_full_params, ProgramError> {
+    fn whisper_full_default_params(&self,
+                                   strategy: whisper_sampling_strategy) -> Result<whisper_full_params, ProgramError> {
         let full_default_params: libloading::Symbol<unsafe extern "C" fn(whisper_sampling_strategy) -> whisper_full_params> = unsafe {
-            self.inner.get(b"whisper_full_default_params\0")?
+            self.library()?.get(b"whisper_full_default_params\0")?
         };
         let params = unsafe {
-            full_default_params(whisper_sampling_strategy_WHISPER_SAMPLING_GREEDY)
+            full_default_params(strategy)
         };
         Ok(params)
     }
 
-    fn whisper_full(&self, wav: &Vec<f32>,
+    fn whisper_full(&self, state: Option<*mut whisper_state>, wav: &Vec<f32>,
                     params: whisper_full_params) -> Result<std::os::raw::c_int, ProgramError> {
-        let whisper_full: libloading::Symbol<unsafe extern "C" fn(
+        let result = match state {
+            Some(state) => {
+                let whisper_full_with_state: libloading::Symbol<unsafe extern "C" fn(
+                    *mut whisper_context,
+                    *mut whisper_state,
+                    whisper_full_params,
+                    *const f32,
+                    std::os::raw::c_int,
+                ) -> std::os::raw::c_int> = unsafe {
+                    self.library()?.get(b"whisper_full_with_state\0")?
+                };
+                unsafe {
+                    whisper_full_with_state(self.get_context()?,
+                                            state,
+                                            params,
+                                            wav.as_ptr(),
+                                            wav.len() as std::os::raw::c_int)
+                }
+            }
+            None => {
+                let whisper_full: libloading::Symbol<unsafe extern "C" fn(
+                    *mut whisper_context,
+                    whisper_full_params,
+                    *const f32,
+                    std::os::raw::c_int,
+                ) -> std::os::raw::c_int> = unsafe {
+                    self.library()?.get(b"whisper_full\0")?
+                };
+                unsafe {
+                    whisper_full(self.get_context()?,
+                                 params,
+                                 wav.as_ptr(),
+                                 wav.len() as std::os::raw::c_int)
+                }
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn whisper_init_state(&self) -> Result<*mut whisper_state, ProgramError> {
+        let init_state: libloading::Symbol<unsafe extern "C" fn(*mut whisper_context) -> *mut whisper_state> = unsafe {
+            self.library()?.get(b"whisper_init_state\0")?
+        };
+        let state = unsafe {
+            init_state(self.get_context()?)
+        };
+        if state.is_null() {
+            return Err(ProgramError::from("Failed to init whisper state"));
+        }
+        Ok(state)
+    }
+
+    fn whisper_free_state(&self, state: *mut whisper_state) -> Result<(), ProgramError> {
+        let free_state: libloading::Symbol<unsafe extern "C" fn(*mut whisper_state)> = unsafe {
+            self.library()?.get(b"whisper_free_state\0")?
+        };
+        unsafe {
+            free_state(state);
+        }
+        Ok(())
+    }
+
+    /// results of `whisper_full` are kept in given state, or in the default state of context if none;
+    /// returns pointer of the holder and suffix of symbols reading results from it
+    fn result_holder(&self,
+                     state: Option<*mut whisper_state>) -> Result<(*mut std::os::raw::c_void, &'static str), ProgramError> {
+        match state {
+            Some(state) => Ok((state as *mut std::os::raw::c_void, "_from_state")),
+            None => Ok((self.get_context()? as *mut std::os::raw::c_void, "")),
+        }
+    }
+
+    /// tokenize text into at most `n_max_tokens` tokens, text of too many tokens is an error
+    fn whisper_tokenize(&self, text: &str, n_max_tokens: usize) -> Result<Vec<whisper_token>, ProgramError> {
+        let tokenize: libloading::Symbol<unsafe extern "C" fn(
             *mut whisper_context,
-            whisper_full_params,
-            *const f32,
+            *const std::os::raw::c_char,
+            *mut whisper_token,
             std::os::raw::c_int,
         ) -> std::os::raw::c_int> = unsafe {
-            self.inner.get(b"whisper_full\0")?
+            self.library()?.get(b"whisper_tokenize\0")?
         };
-
-        let result = unsafe {
-            whisper_full(self.get_context()?,
-                         params,
-                         wav.as_ptr(),
-                         wav.len() as std::os::raw::c_int)
+        let text_ptr = CString::new(text.as_bytes())
+            .map_err(|_| {
+                ProgramError::from("unable to convert String to CString")
+            })?;
+        let mut tokens: Vec<whisper_token> = vec![0; n_max_tokens];
+        let n_tokens = unsafe {
+            tokenize(self.get_context()?,
+                     text_ptr.as_ptr(),
+                     tokens.as_mut_ptr(),
+                     n_max_tokens as std::os::raw::c_int)
         };
-
-        Ok(result)
+        if n_tokens < 0 {
+            return Err(ProgramError::from(format!("failed to tokenize text: {}", text)));
+        }
+        tokens.truncate(n_tokens as usize);
+        Ok(tokens)
     }
 
-    fn whisper_full_n_segments(&self) -> Result<std::os::raw::c_int, ProgramError> {
+    fn whisper_full_n_segments(&self, state: Option<*mut whisper_state>) -> Result<std::os::raw::c_int, ProgramError> {
+        let (holder, suffix) = self.result_holder(state)?;
+        let symbol = format!("whisper_full_n_segments{}\0", suffix);
         let full_n_segments: libloading::Symbol<unsafe extern "C" fn(
-            *mut whisper_context
+            *mut std::os::raw::c_void
         ) -> std::os::raw::c_int> = unsafe {
-            self.inner.get(b"whisper_full_n_segments\0").unwrap()
+            self.library()?.get(symbol.as_bytes())?
         };
         let n_segments = unsafe {
-            full_n_segments(self.get_context()?)
+            full_n_segments(holder)
         };
         Ok(n_segments)
     }
 
-    fn whisper_full_get_segment_text(&self, seg: std::os::raw::c_int) -> Result<String, ProgramError> {
+    fn whisper_full_get_segment_text(&self,
+                                     state: Option<*mut whisper_state>,
+                                     seg: std::os::raw::c_int) -> Result<String, ProgramError> {
+        let (holder, suffix) = self.result_holder(state)?;
+        let symbol = format!("whisper_full_get_segment_text{}\0", suffix);
         let full_get_segment_text: libloading::Symbol<unsafe extern "C" fn(
-            *mut whisper_context,
+            *mut std::os::raw::c_void,
             std::os::raw::c_int,
         ) -> *const std::os::raw::c_char> = unsafe {
-            self.inner.get(b"whisper_full_get_segment_text\0").unwrap()
+            self.library()?.get(symbol.as_bytes())?
         };
         let text = unsafe {
-            full_get_segment_text(self.get_context()?, seg)
+            full_get_segment_text(holder, seg)
         };
         let text = unsafe {
             CStr::from_ptr(text).to_string_lossy().to_string()
@@ -244,12 +532,87 @@ impl WhisperLibrary {
         Ok(text)
     }
 
+    fn whisper_token_eot(&self) -> Result<whisper_token, ProgramError> {
+        let token_eot: libloading::Symbol<unsafe extern "C" fn(
+            *mut whisper_context
+        ) -> whisper_token> = unsafe {
+            self.library()?.get(b"whisper_token_eot\0")?
+        };
+        let token = unsafe {
+            token_eot(self.get_context()?)
+        };
+        Ok(token)
+    }
+
+    /// start or end time of segment, in units of 10ms
+    fn whisper_full_get_segment_time(&self,
+                                     state: Option<*mut whisper_state>,
+                                     seg: std::os::raw::c_int,
+                                     end: bool) -> Result<i64, ProgramError> {
+        let (holder, suffix) = self.result_holder(state)?;
+        let symbol = if end {
+            format!("whisper_full_get_segment_t1{}\0", suffix)
+        } else {
+            format!("whisper_full_get_segment_t0{}\0", suffix)
+        };
+        let get_segment_time: libloading::Symbol<unsafe extern "C" fn(
+            *mut std::os::raw::c_void,
+            std::os::raw::c_int,
+        ) -> i64> = unsafe {
+            self.library()?.get(symbol.as_bytes())?
+        };
+        let time = unsafe {
+            get_segment_time(holder, seg)
+        };
+        Ok(time)
+    }
+
+    fn whisper_full_n_tokens(&self,
+                             state: Option<*mut whisper_state>,
+                             seg: std::os::raw::c_int) -> Result<std::os::raw::c_int, ProgramError> {
+        let (holder, suffix) = self.result_holder(state)?;
+        let symbol = format!("whisper_full_n_tokens{}\0", suffix);
+        let full_n_tokens: libloading::Symbol<unsafe extern "C" fn(
+            *mut std::os::raw::c_void,
+            std::os::raw::c_int,
+        ) -> std::os::raw::c_int> = unsafe {
+            self.library()?.get(symbol.as_bytes())?
+        };
+        let n_tokens = unsafe {
+            full_n_tokens(holder, seg)
+        };
+        Ok(n_tokens)
+    }
+
+    fn whisper_full_get_token_data(&self,
+                                   state: Option<*mut whisper_state>,
+                                   seg: std::os::raw::c_int,
+                                   token: std::os::raw::c_int) -> Result<whisper_token_data, ProgramError> {
+        let (holder, suffix) = self.result_holder(state)?;
+        let symbol = format!("whisper_full_get_token_data{}\0", suffix);
+        let full_get_token_data: libloading::Symbol<unsafe extern "C" fn(
+            *mut std::os::raw::c_void,
+            std::os::raw::c_int,
+            std::os::raw::c_int,
+        ) -> whisper_token_data> = unsafe {
+            self.library()?.get(symbol.as_bytes())?
+        };
+        let data = unsafe {
+            full_get_token_data(holder, seg, token)
+        };
+        Ok(data)
+    }
+
     fn whisper_free(&mut self) -> Result<(), ProgramError> {
         if self.context.is_none() {
             return Ok(());
         }
+        // states must be freed before the context they are created from
+        for state in self.states.take_all() {
+            self.whisper_free_state(state)?;
+        }
         let free: libloading::Symbol<unsafe extern "C" fn(*mut whisper_context)> = unsafe {
-            self.inner.get(b"whisper_free\0").unwrap()
+            self.library()?.get(b"whisper_free\0")?
         };
         unsafe {
             free(self.get_context()?);
@@ -265,11 +628,91 @@ impl WhisperLibrary {
     }
 }
 
+/// check the library exports every bound symbol, and that it's of whisper.cpp v1.2.x or v1.3.x whose
+/// `whisper_full_params` layout matches the declaration above
+fn check_symbols(lib: &libloading::Library) -> Result<WhisperAbi, ProgramError> {
+    let has_symbol = |name: &str| {
+        let symbol = format!("{}\0", name);
+        unsafe { lib.get::<unsafe extern "C" fn()>(symbol.as_bytes()).is_ok() }
+    };
+    let missing: Vec<&str> = REQUIRED_SYMBOLS.iter()
+        .filter(|name| !has_symbol(name))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(ProgramError::from(format!("Whisper library misses symbols: {}", missing.join(", "))));
+    }
+    if UNSUPPORTED_SYMBOLS.iter().any(|name| has_symbol(name)) {
+        return Err(ProgramError::from("Whisper library of v1.4.0 or later is not supported, v1.2.x or v1.3.x is required"));
+    }
+    if STATE_SYMBOLS.iter().all(|name| has_symbol(name)) {
+        Ok(WhisperAbi::V1_3)
+    } else {
+        Ok(WhisperAbi::V1_2)
+    }
+}
+
+/// whisper library file of given path, which is either the library file itself or the directory of it,
+/// library in `whisper` directory is used if no path given
+pub fn library_file(library_path: Option<&str>) -> PathBuf {
+    match library_path.map(str::trim).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if path.is_dir() {
+                path.join(LIBRARY_FILE_NAME)
+            } else {
+                path
+            }
+        }
+        None => PathBuf::from(LIBRARY_DIR).join(LIBRARY_FILE_NAME)
+    }
+}
+
+async fn update_library_status(status: WhisperLibraryStatus) {
+    app::silent_emit_all(constants::event::ON_WHISPER_LIBRARY_STATUS, status.clone());
+    *LIBRARY_STATUS.write().await = status;
+}
+
+/// load whisper library of given path and report the result to frontend
+async fn load_library(lib: &mut WhisperLibrary, library_path: Option<&str>) -> Result<(), ProgramError> {
+    let file = library_file(library_path);
+    let path = file.to_string_lossy().to_string();
+    match lib.load(&file) {
+        Ok(_) => {
+            let system_info = lib.whisper_print_system_info().ok();
+            log::debug!("Load whisper library {} success, abi: {:?}, system info: {:?}", path, lib.abi, system_info);
+            update_library_status(WhisperLibraryStatus {
+                available: true,
+                path,
+                system_info,
+                concurrent: lib.abi == WhisperAbi::V1_3,
+                error: None,
+            }).await;
+            Ok(())
+        }
+        Err(err) => {
+            log::error!("Whisper library {} is unavailable, err: {}", path, err);
+            update_library_status(WhisperLibraryStatus {
+                available: false,
+                path,
+                system_info: None,
+                concurrent: false,
+                error: Some(err.to_string()),
+            }).await;
+            Err(err)
+        }
+    }
+}
+
+pub async fn library_status() -> WhisperLibraryStatus {
+    LIBRARY_STATUS.read().await.clone()
+}
+
 /// try to check and init whisper library if whisper recognition is enabled;
 /// this method should be called at app startup.
 pub async fn init_library() {
     let lock = WHISPER_LIB.clone();
-    let _ = lock.lock().await;
+    let _ = lock.write().await;
     match voice_recognition::load_voice_recognition_config() {
         Ok(config) => {
             if !config.enable {
@@ -286,8 +729,9 @@ pub async fn init_library() {
 
             // spawn a new thread to load model
             let model = config.use_model.clone();
+            let library_path = config.library_path.clone();
             tauri::async_runtime::spawn(async move {
-                match load_model(model.clone()).await {
+                match load_model(model.clone(), library_path).await {
                     Ok(_) => {}
                     Err(err) => {
                         log::error!("Load model {} failed with error: {}", model, err);
@@ -303,40 +747,29 @@ pub async fn init_library() {
 
 /// load whisper with given model name, note that model name pattern is "ggml-\[name].bin",
 /// the param is the part "\[name]",
-/// for example: to load model "ggml-base.bin", pass param: "base"
-pub async fn load_model(model: String) -> Result<(), ProgramError> {
+/// for example: to load model "ggml-base.bin", pass param: "base";
+/// the library is loaded from `library_path` first if it's not loaded yet
+pub async fn load_model(model: String, library_path: Option<String>) -> Result<(), ProgramError> {
     log::debug!("Load whisper model: {}", model.clone());
     // lock download file by lib lock
     let lock = WHISPER_LIB.clone();
-    let mut lib = lock.lock().await;
+    let mut lib = lock.write().await;
+    load_library(&mut lib, library_path.as_deref()).await?;
 
     let (interrupted_tx, _) = &*MODEL_LOAD_STOP_SIG;
     let mut interrupted_rx = interrupted_tx.subscribe();
 
-    let model_name = format!("ggml-{}.bin", model);
+    let model_name = whisper_models::model_file_name(&model);
     log::debug!("Loading whisper model {}", model_name.clone());
 
-    let model_path = PathBuf::from(MODEL_PATH);
-    std::fs::create_dir_all(model_path.clone())?;
-
-    let download_tmp_file = model_path.join(MODEL_TMP_FILE);
-    let model_file = model_path.join(model_name.clone());
+    let download_tmp_file = whisper_models::model_tmp_file(&model);
+    let model_file = whisper_models::model_file(&model);
 
     tokio::select! {
         response = async {
             if !model_file.is_file() {
-                // TODO: try to compare model sha256 to make sure file is ok
-                let download_url = DLL_DOWNLOAD_URL.to_owned() + &*model_name.clone();
-                // download if model not downloaded
-                log::debug!("Downloading whisper model {} from path [{}], download to {}",
-                    model_name.clone(),
-                    download_url.clone(),
-                    download_tmp_file.clone().to_str().unwrap());
-
-                http::download(download_url, download_tmp_file.clone()).await?;
-                log::debug!("Download model file {} success", model_name.clone());
-                // rename tmp file to actual model file
-                std::fs::rename(download_tmp_file.clone(), model_file.clone())?;
+                // download if model not downloaded, checksum is verified before it's used
+                whisper_models::download_model(&model).await?;
             }
 
             // try to load model
@@ -370,20 +803,20 @@ pub async fn free_model() -> Result<(), ProgramError> {
     }
 
     // lock method by lib lock
-    // this will wait for load_model to be finished since we have set stop signal
+    // this will wait for load_model and running recognitions to be finished since we have set stop signal
     let lock = WHISPER_LIB.clone();
-    let mut lib = lock.lock().await;
+    let mut lib = lock.write().await;
     // try to free first
     lib.whisper_free()
 }
 
-/// update whisper model if choosing another model
-pub async fn update_model(model: String) -> Result<(), ProgramError> {
+/// update whisper model if choosing another model or library
+pub async fn update_model(model: String, library_path: Option<String>) -> Result<(), ProgramError> {
     free_model().await?;
 
     // spawn a new thread to load model
     tauri::async_runtime::spawn(async move {
-        match load_model(model.clone()).await {
+        match load_model(model.clone(), library_path).await {
             Ok(_) => {}
             Err(err) => {
                 log::error!("Update model {} failed with error: {}", model, err);
@@ -394,28 +827,214 @@ pub async fn update_model(model: String) -> Result<(), ProgramError> {
     Ok(())
 }
 
+/// a recognized segment of whisper
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Segment {
+    pub text: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    // average probability of text tokens in this segment
+    pub probability: f32,
+    // probability is lower than configured min confidence
+    pub low_confidence: bool,
+}
+
+/// read segment of last transcribe, special tokens like timestamps are not counted
+/// into segment probability
+fn get_segment(lib: &WhisperLibrary,
+               state: Option<*mut whisper_state>,
+               i: std::os::raw::c_int,
+               token_eot: whisper_token,
+               min_confidence: f32) -> Result<Segment, ProgramError> {
+    let text = lib.whisper_full_get_segment_text(state, i)?;
+    let start_ms = lib.whisper_full_get_segment_time(state, i, false)? * 10;
+    let end_ms = lib.whisper_full_get_segment_time(state, i, true)? * 10;
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for j in 0..lib.whisper_full_n_tokens(state, i)? {
+        let token = lib.whisper_full_get_token_data(state, i, j)?;
+        if token.id >= token_eot {
+            continue;
+        }
+        total += token.p;
+        count += 1;
+    }
+    let probability = if count > 0 { total / count as f32 } else { 0.0 };
+
+    Ok(Segment {
+        text,
+        start_ms,
+        end_ms,
+        probability,
+        low_confidence: probability < min_confidence,
+    })
+}
+
+fn collect_segments(lib: &WhisperLibrary,
+                    state: Option<*mut whisper_state>,
+                    min_confidence: f32) -> Result<Vec<Segment>, ProgramError> {
+    let token_eot = lib.whisper_token_eot()?;
+    let n_segments = lib.whisper_full_n_segments(state)?;
+    let mut segments = vec![];
+    for i in 0..n_segments {
+        segments.push(get_segment(lib, state, i, token_eot, min_confidence)?);
+    }
+    Ok(segments)
+}
+
+/// user data of [new_segment_callback], it lives on the stack of [recognize_segments]
+/// until transcribe finishes
+struct NewSegmentCallbackData<'a> {
+    lib: &'a WhisperLibrary,
+    state: Option<*mut whisper_state>,
+    token_eot: whisper_token,
+    min_confidence: f32,
+    sender: UnboundedSender<Segment>,
+}
+
+/// called by whisper from inside `whisper_full` whenever new segments are decoded,
+/// segments are pushed into the channel given to [recognize_segments]
+unsafe extern "C" fn new_segment_callback(_ctx: *mut whisper_context,
+                                          n_new: std::os::raw::c_int,
+                                          user_data: *mut std::os::raw::c_void) {
+    let data = &*(user_data as *const NewSegmentCallbackData);
+    let n_segments = match data.lib.whisper_full_n_segments(data.state) {
+        Ok(n_segments) => n_segments,
+        Err(err) => {
+            log::error!("Failed to get whisper segments in callback, err: {}", err);
+            return;
+        }
+    };
+    for i in (n_segments - n_new).max(0)..n_segments {
+        match get_segment(data.lib, data.state, i, data.token_eot, data.min_confidence) {
+            Ok(segment) => {
+                // receiver may be dropped if nobody cares about partial results anymore
+                let _ = data.sender.send(segment);
+            }
+            Err(err) => {
+                log::error!("Failed to get whisper segment {} in callback, err: {}", i, err);
+            }
+        }
+    }
+}
+
+/// [new_segment_callback] of v1.3 signature, the state is also kept in user data
+unsafe extern "C" fn new_segment_callback_with_state(ctx: *mut whisper_context,
+                                                     _state: *mut whisper_state,
+                                                     n_new: std::os::raw::c_int,
+                                                     user_data: *mut std::os::raw::c_void) {
+    new_segment_callback(ctx, n_new, user_data)
+}
+
+/// join text of segments, segments of low confidence are dropped
+pub fn segments_text(segments: &[Segment]) -> String {
+    let mut result = String::new();
+    for segment in segments {
+        if segment.low_confidence {
+            log::debug!("Drop whisper segment of low confidence {}: {}",
+                segment.probability, segment.text);
+            continue;
+        }
+        result.push_str(&segment.text);
+    }
+    result
+}
+
 /// a united function to do transcribe of whisper,
-/// all transcribe params are set by default optimized values,
-/// all you need to offer is language and audio data.<br>
+/// transcribe params not set in `decode` of config are set by default optimized values.<br>
 /// note data must be Vec\<f32>, mono channel, sample rate: 16000
-pub async fn recognize(language: Option<String>, data: &Vec<f32>) -> Result<String, ProgramError> {
+pub async fn recognize(config: &RecognizeByWhisper, data: &Vec<f32>) -> Result<String, ProgramError> {
+    let segments = recognize_segments(config, data, None).await?;
+    Ok(segments_text(&segments))
+}
+
+// encoder context of the whole 30s window whisper is trained on
+const FULL_AUDIO_CTX: i32 = 1500;
+
+/// decode params of transcribing audio files, which keeps every segment with its own timestamps
+/// and encodes the whole window, since the shortened context of live speech drops text of long windows
+pub fn file_transcription_config(config: &RecognizeByWhisper) -> RecognizeByWhisper {
+    let mut config = config.clone();
+    config.decode = file_transcription_decode(&config.decode);
+    config
+}
+
+fn file_transcription_decode(decode: &WhisperDecodeParams) -> WhisperDecodeParams {
+    let mut decode = decode.clone();
+    decode.single_segment = Some(false);
+    decode.audio_ctx = Some(FULL_AUDIO_CTX);
+    decode
+}
+
+/// transcribe by whisper and return segments with timestamps and confidence,
+/// if `segment_tx` is given, segments are also sent to it one by one while transcribing
+pub async fn recognize_segments(config: &RecognizeByWhisper,
+                                data: &Vec<f32>,
+                                segment_tx: Option<UnboundedSender<Segment>>) -> Result<Vec<Segment>, ProgramError> {
     if !MODEL_AVAILABLE.load(Ordering::Acquire) {
+        let status = library_status().await;
+        if let Some(err) = status.error {
+            return Err(ProgramError::from(format!("Whisper library {} is unavailable, err: {}", status.path, err)));
+        }
         return Err(ProgramError::from("Whisper model is not loaded"));
     }
 
     let lock = WHISPER_LIB.clone();
-    let lib = lock.lock().await;
+    let lib = lock.read().await;
 
-    let mut wparams = lib.whisper_full_default_params()?;
+    // one model is shared by recognitions each running on its own state,
+    // or they are serialized on the default state of context
+    let _context_guard = match lib.abi {
+        WhisperAbi::V1_2 => Some(lib.context_lock.lock().await),
+        WhisperAbi::V1_3 => None,
+    };
+    let pooled_state = match lib.abi {
+        WhisperAbi::V1_2 => None,
+        WhisperAbi::V1_3 => Some(lib.states.acquire(&lib, config.state_pool_size).await?),
+    };
+    let state = pooled_state.as_ref().map(|pooled| pooled.state);
+
+    let decode = &config.decode;
+    let strategy = match decode.strategy {
+        Some(WhisperSamplingStrategy::BeamSearch) => whisper_sampling_strategy_WHISPER_SAMPLING_BEAM_SEARCH,
+        _ => whisper_sampling_strategy_WHISPER_SAMPLING_GREEDY,
+    };
+    let mut wparams = lib.whisper_full_default_params(strategy)?;
     wparams.print_realtime = false;
     wparams.print_progress = false;
     wparams.print_timestamps = false;
     wparams.print_special = false;
-    wparams.no_context = true;
-    wparams.single_segment = true;
-    wparams.translate = false;
+    wparams.no_context = decode.no_context.unwrap_or(true);
+    wparams.single_segment = decode.single_segment.unwrap_or(true);
+    wparams.translate = config.task == WhisperTask::Translate;
+
+    if let Some(best_of) = decode.best_of {
+        wparams.greedy.best_of = best_of;
+    }
+    if let Some(beam_size) = decode.beam_size {
+        wparams.beam_search.beam_size = beam_size;
+    }
+    if let Some(patience) = decode.patience {
+        wparams.beam_search.patience = patience;
+    }
+    if let Some(temperature) = decode.temperature {
+        wparams.temperature = temperature;
+    }
+    if let Some(temperature_inc) = decode.temperature_inc {
+        wparams.temperature_inc = temperature_inc;
+    }
+    if let Some(no_speech_thold) = decode.no_speech_thold {
+        wparams.no_speech_thold = no_speech_thold;
+    }
+    if let Some(logprob_thold) = decode.logprob_thold {
+        wparams.logprob_thold = logprob_thold;
+    }
+    if let Some(suppress_blank) = decode.suppress_blank {
+        wparams.suppress_blank = suppress_blank;
+    }
 
-    let lan: CString = if let Some(language) = language {
+    let lan: CString = if let Some(language) = config.language.clone() {
         CString::new(language)
             .map_err(|_| "unable to parse string to cstring")?
     } else {
@@ -425,7 +1044,28 @@ pub async fn recognize(language: Option<String>, data: &Vec<f32>) -> Result<Stri
 
     wparams.language = lan.as_ptr();
 
-    let n_threads = std::cmp::min(4, num_cpus::get());
+    // tokens must outlive the transcribe call since params only keep a pointer to them
+    let prompt_tokens = match config.prompt() {
+        Some(prompt) => {
+            // a token is never shorter than one byte, so text length is large enough for the buffer
+            let mut tokens = lib.whisper_tokenize(&prompt, prompt.len() + 1)?;
+            // whisper only looks at the last half of text context as prompt
+            if tokens.len() > MAX_PROMPT_TOKENS {
+                tokens.drain(..tokens.len() - MAX_PROMPT_TOKENS);
+            }
+            tokens
+        }
+        None => vec![]
+    };
+    if !prompt_tokens.is_empty() {
+        wparams.prompt_tokens = prompt_tokens.as_ptr();
+        wparams.prompt_n_tokens = prompt_tokens.len() as std::os::raw::c_int;
+    }
+
+    let n_threads = match decode.n_threads {
+        Some(n_threads) if n_threads > 0 => n_threads as usize,
+        _ => std::cmp::min(4, num_cpus::get()),
+    };
     wparams.n_threads = n_threads as std::os::raw::c_int;
 
     wparams.speed_up = false;
@@ -433,73 +1073,98 @@ pub async fn recognize(language: Option<String>, data: &Vec<f32>) -> Result<Stri
     // the original value is 1500(corresponds to 30s audio), and the value should be multiple of 64;
     // setting it to 768 would make the Encoder evaluate about 2 times faster;
     // refer: https://github.com/ggerganov/whisper.cpp/discussions/297
-    wparams.audio_ctx = 768;
+    wparams.audio_ctx = decode.audio_ctx.unwrap_or(768);
 
-    let result = lib.whisper_full(data, wparams)?;
-    if result != 0 {
-        return Err(ProgramError::from(format!("transcribe failed, code {}", result)));
+    let token_eot = lib.whisper_token_eot()?;
+    let callback_data = segment_tx.map(|sender| NewSegmentCallbackData {
+        lib: &lib,
+        state,
+        token_eot,
+        min_confidence: config.min_confidence,
+        sender,
+    });
+    if let Some(callback_data) = &callback_data {
+        wparams.new_segment_callback = match lib.abi {
+            WhisperAbi::V1_2 => Some(new_segment_callback),
+            WhisperAbi::V1_3 => unsafe {
+                std::mem::transmute::<whisper_new_segment_callback_with_state, whisper_new_segment_callback>(
+                    Some(new_segment_callback_with_state))
+            },
+        };
+        wparams.new_segment_callback_user_data = callback_data as *const NewSegmentCallbackData
+            as *mut std::os::raw::c_void;
     }
 
-    let n_segments = lib.whisper_full_n_segments()?;
-    let mut result = String::new();
-    for i in 0..n_segments {
-        let text = lib.whisper_full_get_segment_text(i)?;
-        result.push_str(&*text);
+    // decoding takes seconds, let the runtime move other tasks off this worker meanwhile,
+    // the model and state stay borrowed from the guards above until it returns
+    let result = tokio::task::block_in_place(|| lib.whisper_full(state, data, wparams))?;
+    drop(callback_data);
+    if result != 0 {
+        return Err(ProgramError::from(format!("transcribe failed, code {}", result)));
     }
 
-    if is_special_text(&*result) {
-        log::debug!("Whisper segment text is special text {}, skip", result.clone());
-        return Ok(String::new());
-    }
+    collect_segments(&lib, state, config.min_confidence)
+}
 
-    Ok(result)
+/// list names of all models in [whisper_models::MODEL_PATH]
+pub fn available_models() -> Result<Vec<String>, ProgramError> {
+    let models = whisper_models::installed_models()?;
+    Ok(models.into_iter().map(|model| model.name).collect())
 }
 
-/// if whisper return text like: \[Music] (Whisper), it means it is not regular content
-/// of somebody speaking, aka special text
-/// note: whisper lib set special_text=false should work,
-/// but for somehow it is still trying to send back text like this when you give it an empty audio
-fn is_special_text(text: &str) -> bool {
-    let text_heard = String::from(text.trim());
-    {
-        let re = Regex::new(r"\(.*?\)").unwrap();
-        if re.is_match(&*text_heard) {
-            return true;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_file() {
+        assert_eq!(library_file(None), PathBuf::from(LIBRARY_DIR).join(LIBRARY_FILE_NAME));
+        assert_eq!(library_file(Some(" ")), PathBuf::from(LIBRARY_DIR).join(LIBRARY_FILE_NAME));
+        let dir = std::env::temp_dir();
+        assert_eq!(library_file(dir.to_str()), dir.join(LIBRARY_FILE_NAME));
+        assert_eq!(library_file(Some("lib/custom-whisper.so")), PathBuf::from("lib/custom-whisper.so"));
     }
 
-    {
-        let re = Regex::new(r"\[.*?\]").unwrap();
-        if re.is_match(&*text_heard) {
-            return true;
-        }
+    #[test]
+    fn test_load_missing_library() {
+        let mut lib = WhisperLibrary::new();
+        let file = std::env::temp_dir().join("not-exists").join(LIBRARY_FILE_NAME);
+        assert!(lib.load(&file).is_err());
+        assert!(lib.library().is_err());
+        assert!(lib.whisper_full_default_params(whisper_sampling_strategy_WHISPER_SAMPLING_GREEDY).is_err());
     }
 
-    false
-}
+    #[test]
+    fn test_file_transcription_config() {
+        let mut decode = WhisperDecodeParams::default();
+        decode.single_segment = Some(true);
+        decode.audio_ctx = Some(768);
+        decode.beam_size = Some(5);
+        let decode = file_transcription_decode(&decode);
+        assert_eq!(decode.single_segment, Some(false));
+        assert_eq!(decode.audio_ctx, Some(FULL_AUDIO_CTX));
+        assert_eq!(decode.beam_size, Some(5));
+    }
 
-/// list all models in [MODEL_PATH] that is end with .bin(which is a file suffix of whisper model)
-pub fn available_models() -> Result<Vec<String>, ProgramError> {
-    let model_path = PathBuf::from(MODEL_PATH);
-    let mut models = vec![];
+    #[tokio::test]
+    async fn test_state_pool_bounded() {
+        let lib = WhisperLibrary::new();
+        // states are never dereferenced by the pool, fake ones are enough
+        let mut fake_states = [0u8; 2];
+        {
+            let mut inner = lib.states.inner.lock();
+            inner.idle = fake_states.iter_mut().map(|b| b as *mut u8 as *mut whisper_state).collect();
+            inner.created = 2;
+        }
 
-    let file_name_p = regex::Regex::new(r"ggml-(\w+).bin").unwrap();
+        let first = lib.states.acquire(&lib, 1).await.unwrap();
+        let waiting = tokio::time::timeout(std::time::Duration::from_millis(50), lib.states.acquire(&lib, 1)).await;
+        assert!(waiting.is_err());
+        drop(first);
+        let second = tokio::time::timeout(std::time::Duration::from_millis(50), lib.states.acquire(&lib, 1)).await;
+        assert!(second.is_ok());
 
-    for entry in walkdir::WalkDir::new(model_path)
-        .max_depth(1)
-        .into_iter()
-        .filter_map(|e| e.ok()) {
-        if !entry.file_type().is_file() {
-            continue;
-        }
-        let file_name = entry.file_name().to_str();
-        if file_name.is_some() {
-            let file_name = file_name.unwrap();
-            if let Some(captures) = file_name_p.captures(file_name) {
-                let name = &captures[1];
-                models.push(name.to_string());
-            }
-        }
+        drop(second);
+        assert_eq!(lib.states.take_all().len(), 2);
     }
-    Ok(models)
 }
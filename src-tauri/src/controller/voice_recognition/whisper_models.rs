@@ -0,0 +1,332 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::regex::Regex;
+
+use crate::controller::errors::ProgramError;
+use crate::utils;
+use crate::utils::http;
+
+pub const MODEL_PATH: &str = "whisper/models";
+
+const BUNDLED_CATALOG: &str = include_str!("whisper_models.tsv");
+// catalog refreshed from huggingface, saved in the format of the bundled one
+const CATALOG_FILE_NAME: &str = "catalog.tsv";
+
+const MODEL_REPO_TREE_API: &str = "https://huggingface.co/api/models/ggerganov/whisper.cpp/tree/main";
+const MODEL_DOWNLOAD_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/";
+
+lazy_static! {
+    // covers names like base, base.en, large-v2, large-v3-turbo, small.en-tdrz and base-q5_1
+    static ref MODEL_FILE_NAME: Regex = Regex::new(r"^ggml-([\w.\-]+)\.bin$").unwrap();
+    static ref QUANTIZATION: Regex = Regex::new(r"-(q\d+_\w+)$").unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoTreeLfs {
+    // sha256 of the file content
+    oid: String,
+    size: u64,
+}
+
+/// entry of huggingface repo tree api
+#[derive(Debug, Deserialize)]
+struct RepoTreeEntry {
+    #[serde(rename = "type")]
+    entry_type: String,
+    path: String,
+    size: u64,
+    lfs: Option<RepoTreeLfs>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhisperModelInfo {
+    pub name: String,
+    pub file_name: String,
+    pub size: u64,
+    pub sha256: Option<String>,
+    // english only models are named with `.en`
+    pub multilingual: bool,
+    // quantization type like q5_0, none for full precision models
+    pub quantization: Option<String>,
+    pub installed: bool,
+}
+
+/// model name of file name, e.g. "ggml-base-q5_1.bin" => "base-q5_1"
+pub fn model_name(file_name: &str) -> Option<String> {
+    MODEL_FILE_NAME.captures(file_name).map(|captures| captures[1].to_string())
+}
+
+pub fn model_file_name(name: &str) -> String {
+    format!("ggml-{}.bin", name)
+}
+
+pub fn model_file(name: &str) -> PathBuf {
+    PathBuf::from(MODEL_PATH).join(model_file_name(name))
+}
+
+/// file model downloaded into before its checksum is verified
+pub fn model_tmp_file(name: &str) -> PathBuf {
+    PathBuf::from(MODEL_PATH).join(format!("{}.tmp", model_file_name(name)))
+}
+
+fn model_info(name: String, size: u64, sha256: Option<String>) -> WhisperModelInfo {
+    let base_name = QUANTIZATION.replace(&name, "").to_string();
+    WhisperModelInfo {
+        file_name: model_file_name(&name),
+        size,
+        sha256,
+        multilingual: !base_name.ends_with(".en") && !base_name.contains(".en-"),
+        quantization: QUANTIZATION.captures(&name).map(|captures| captures[1].to_string()),
+        installed: model_file(&name).is_file(),
+        name,
+    }
+}
+
+fn parse_catalog(content: &str) -> Vec<WhisperModelInfo> {
+    let mut models: Vec<WhisperModelInfo> = content.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t').map(str::trim);
+            let name = fields.next()?.to_string();
+            let size = fields.next()?.parse::<u64>().ok()?;
+            let sha256 = fields.next()
+                .filter(|sha256| !sha256.is_empty() && *sha256 != "-")
+                .map(|sha256| sha256.to_string());
+            Some(model_info(name, size, sha256))
+        })
+        .collect();
+    models.sort_by_key(|model| model.size);
+    models
+}
+
+fn format_catalog(models: &[WhisperModelInfo]) -> String {
+    let mut content = String::from("# whisper.cpp models refreshed from huggingface: <name>\t<size in bytes>\t<sha256 or ->\n");
+    for model in models {
+        content.push_str(&format!("{}\t{}\t{}\n", model.name, model.size, model.sha256.as_deref().unwrap_or("-")));
+    }
+    content
+}
+
+/// models of the last refreshed catalog, or the bundled one if never refreshed
+pub fn catalog_models() -> Vec<WhisperModelInfo> {
+    let catalog_file = PathBuf::from(MODEL_PATH).join(CATALOG_FILE_NAME);
+    if let Ok(content) = std::fs::read_to_string(&catalog_file) {
+        let models = parse_catalog(&content);
+        if !models.is_empty() {
+            return models;
+        }
+    }
+    parse_catalog(BUNDLED_CATALOG)
+}
+
+fn save_catalog(models: &[WhisperModelInfo]) -> Result<(), ProgramError> {
+    std::fs::create_dir_all(MODEL_PATH)?;
+    std::fs::write(PathBuf::from(MODEL_PATH).join(CATALOG_FILE_NAME), format_catalog(models))?;
+    Ok(())
+}
+
+fn parse_repo_tree(entries: Vec<RepoTreeEntry>) -> Vec<WhisperModelInfo> {
+    let mut models: Vec<WhisperModelInfo> = entries.into_iter()
+        .filter(|entry| entry.entry_type == "file")
+        .filter_map(|entry| {
+            let name = model_name(&entry.path)?;
+            let (size, sha256) = match entry.lfs {
+                Some(lfs) => (lfs.size, Some(lfs.oid)),
+                None => (entry.size, None)
+            };
+            Some(model_info(name, size, sha256))
+        })
+        .collect();
+    models.sort_by_key(|model| model.size);
+    models
+}
+
+/// fetch models published in whisper.cpp huggingface repo and refresh the local catalog with them
+async fn refresh_catalog() -> Result<Vec<WhisperModelInfo>, ProgramError> {
+    let entries = http::get_json::<Vec<RepoTreeEntry>>(MODEL_REPO_TREE_API.to_string()).await?;
+    let models = parse_repo_tree(entries);
+    if let Err(err) = save_catalog(&models) {
+        log::error!("Failed to save whisper model catalog, err: {}", err);
+    }
+    Ok(models)
+}
+
+/// list all models published in whisper.cpp huggingface repo,
+/// the local catalog is listed if the repo is unreachable
+pub async fn remote_models() -> Result<Vec<WhisperModelInfo>, ProgramError> {
+    match refresh_catalog().await {
+        Ok(models) => Ok(models),
+        Err(err) => {
+            log::warn!("Failed to refresh whisper model catalog, list local one, err: {}", err);
+            Ok(catalog_models())
+        }
+    }
+}
+
+/// list all models in [MODEL_PATH], checksums are not calculated since models are large
+pub fn installed_models() -> Result<Vec<WhisperModelInfo>, ProgramError> {
+    let model_path = PathBuf::from(MODEL_PATH);
+    let mut models = vec![];
+
+    for entry in walkdir::WalkDir::new(model_path)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str().and_then(model_name) {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            models.push(model_info(name, size, None));
+        }
+    }
+    Ok(models)
+}
+
+pub fn file_sha256(file: &Path) -> Result<String, ProgramError> {
+    let mut file = File::open(file)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+async fn verify_file(file: PathBuf, sha256: String) -> Result<bool, ProgramError> {
+    let hash = tauri::async_runtime::spawn_blocking(move || file_sha256(&file)).await??;
+    Ok(hash.eq_ignore_ascii_case(&sha256))
+}
+
+/// model in the local catalog, the catalog is refreshed only for models published after it
+/// or without checksum, and the local entry is kept if the repo is unreachable
+async fn find_catalog_model(name: &str) -> Result<WhisperModelInfo, ProgramError> {
+    let local = catalog_models().into_iter().find(|model| model.name == name);
+    if let Some(model) = &local {
+        if model.sha256.is_some() {
+            return Ok(model.clone());
+        }
+    }
+    match refresh_catalog().await {
+        Ok(models) => models.into_iter().find(|model| model.name == name),
+        Err(err) => {
+            log::warn!("Failed to refresh whisper model catalog, err: {}", err);
+            local
+        }
+    }.ok_or_else(|| ProgramError::from(format!("Whisper model {} not found in catalog", name)))
+}
+
+/// download model into [MODEL_PATH] and verify its checksum by the catalog,
+/// a file failed to pass verification is removed
+pub async fn download_model(name: &str) -> Result<(), ProgramError> {
+    let model = find_catalog_model(name).await?;
+
+    std::fs::create_dir_all(MODEL_PATH)?;
+    let model_file = model_file(name);
+    let download_tmp_file = model_tmp_file(name);
+    let download_url = MODEL_DOWNLOAD_URL.to_owned() + &model.file_name;
+    log::debug!("Downloading whisper model {} from path [{}], download to {}",
+        name,
+        download_url.clone(),
+        download_tmp_file.to_string_lossy());
+
+    if let Err(err) = http::download(download_url, download_tmp_file.clone()).await {
+        utils::silent_remove_file(download_tmp_file);
+        return Err(err);
+    }
+    match model.sha256 {
+        Some(sha256) => {
+            if !verify_file(download_tmp_file.clone(), sha256).await? {
+                utils::silent_remove_file(download_tmp_file);
+                return Err(ProgramError::from(format!("Checksum of downloaded whisper model {} mismatch", name)));
+            }
+        }
+        None => log::warn!("No checksum of whisper model {} in catalog, skip verification", name)
+    }
+    // rename tmp file to actual model file
+    std::fs::rename(download_tmp_file, model_file)?;
+    log::debug!("Download model file {} success", name);
+    Ok(())
+}
+
+/// check installed model against checksum of the catalog,
+/// a model without known checksum is unverifiable and reported as error
+pub async fn verify_model(name: &str) -> Result<bool, ProgramError> {
+    let model_file = model_file(name);
+    if !model_file.is_file() {
+        return Ok(false);
+    }
+    let model = find_catalog_model(name).await?;
+    match model.sha256 {
+        Some(sha256) => verify_file(model_file, sha256).await,
+        None => Err(ProgramError::from(format!("Whisper model {} is unverifiable, no checksum in catalog", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_name() {
+        assert_eq!(model_name("ggml-base.bin"), Some("base".to_string()));
+        assert_eq!(model_name("ggml-large-v2.bin"), Some("large-v2".to_string()));
+        assert_eq!(model_name("ggml-base.en-q5_1.bin"), Some("base.en-q5_1".to_string()));
+        assert_eq!(model_name("ggml-base.bin.tmp"), None);
+        assert_eq!(model_name("for-tests-ggml-base.bin"), None);
+    }
+
+    #[test]
+    fn test_parse_repo_tree() {
+        let entries: Vec<RepoTreeEntry> = serde_json::from_str(r#"[
+            {"type": "file", "oid": "a", "size": 1000, "path": "README.md"},
+            {"type": "file", "oid": "b", "size": 134, "path": "ggml-base.en-q5_1.bin",
+             "lfs": {"oid": "4baf70dd", "size": 59707625, "pointerSize": 134}},
+            {"type": "file", "oid": "c", "size": 134, "path": "ggml-tiny.bin",
+             "lfs": {"oid": "be07e048", "size": 77691713, "pointerSize": 134}},
+            {"type": "directory", "oid": "d", "size": 0, "path": "models"}
+        ]"#).unwrap();
+        let models = parse_repo_tree(entries);
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].name, "base.en-q5_1");
+        assert_eq!(models[0].sha256, Some("4baf70dd".to_string()));
+        assert!(!models[0].multilingual);
+        assert_eq!(models[0].quantization, Some("q5_1".to_string()));
+        assert_eq!(models[1].name, "tiny");
+        assert_eq!(models[1].size, 77691713);
+        assert!(models[1].multilingual);
+        assert_eq!(models[1].quantization, None);
+    }
+
+    #[test]
+    fn test_bundled_catalog() {
+        let models = parse_catalog(BUNDLED_CATALOG);
+        assert!(models.len() >= 11);
+        let base = models.iter().find(|model| model.name == "base").unwrap();
+        assert!(base.multilingual);
+        assert_eq!(base.sha256, None);
+        assert!(!models.iter().find(|model| model.name == "base.en").unwrap().multilingual);
+    }
+
+    #[test]
+    fn test_format_catalog() {
+        let models = vec![model_info("base-q5_1".to_string(), 59707625, Some("4baf70dd".to_string())),
+                          model_info("tiny".to_string(), 77691713, None)];
+        let parsed = parse_catalog(&format_catalog(&models));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name, "base-q5_1");
+        assert_eq!(parsed[0].sha256, Some("4baf70dd".to_string()));
+        assert_eq!(parsed[0].quantization, Some("q5_1".to_string()));
+        assert_eq!((parsed[1].name.as_str(), parsed[1].size, parsed[1].sha256.clone()), ("tiny", 77691713, None));
+    }
+}
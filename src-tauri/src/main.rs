@@ -132,8 +132,14 @@ fn main() {
             commands::configs::save_voice_engine_config,
             commands::configs::get_auto_translation_config,
             commands::configs::save_auto_translation_config,
+            commands::configs::get_translation_glossary,
+            commands::configs::save_translation_glossary,
+            commands::configs::get_text_processing_config,
+            commands::configs::save_text_processing_config,
             commands::configs::get_voice_recognition_config,
             commands::configs::save_voice_recognition_config,
+            commands::configs::get_talk_params,
+            commands::configs::save_talk_params,
             commands::configs::get_audio_config,
             commands::configs::change_output_device,
             commands::configs::change_input_device,
@@ -155,6 +161,13 @@ fn main() {
             commands::audios::is_recorder_recording,
 
             commands::whisper::whisper_available_models,
+            commands::whisper::whisper_remote_models,
+            commands::whisper::whisper_installed_models,
+            commands::whisper::whisper_install_model,
+            commands::whisper::whisper_verify_model,
+            commands::whisper::whisper_library_status,
+            commands::vosk::vosk_available_models,
+            commands::transcriber::transcribe_file,
         ])
         .system_tray(create_system_tray())
         .on_system_tray_event(handle_system_tray_event)
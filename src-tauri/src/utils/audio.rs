@@ -14,3 +14,23 @@ pub fn convert_to_mono(samples: &Vec<f32>, channels: u16) -> Vec<f32> {
     }
     converted
 }
+
+/// encode mono samples into 16-bit PCM wav file bytes
+pub fn encode_wav(samples: &Vec<f32>, sample_rate: u32) -> Result<Vec<u8>, hound::Error> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = std::io::Cursor::new(Vec::with_capacity(44 + samples.len() * 2));
+    {
+        let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+    }
+    Ok(cursor.into_inner())
+}
@@ -13,6 +13,7 @@ use winapi::um::processthreadsapi::TerminateProcess;
 use winapi::um::psapi::EnumProcesses;
 use winapi::um::psapi::GetProcessImageFileNameW;
 use winapi::um::winnt::{HANDLE, PROCESS_QUERY_INFORMATION, PROCESS_TERMINATE};
+use winapi::um::winuser::GetAsyncKeyState;
 
 #[cfg(target_os = "windows")]
 pub fn process_exists(name: &str, path: &str) -> (bool, DWORD) {
@@ -89,3 +90,60 @@ pub fn terminate_process(pid: DWORD) -> Result<(), String> {
     unsafe { CloseHandle(handle) };
     Ok(())
 }
+
+/// virtual-key code of the main key of shortcut accelerator like "CmdOrCtrl+Shift+F1"
+pub fn accelerator_key_code(accelerator: &str) -> Option<i32> {
+    let key = accelerator.rsplit('+').next()?.trim().to_uppercase();
+    let key = key.strip_prefix("KEY").or_else(|| key.strip_prefix("DIGIT"))
+        .filter(|rest| rest.len() == 1)
+        .unwrap_or(&key);
+    let code = match key {
+        "SPACE" => 0x20,
+        "ENTER" | "RETURN" => 0x0D,
+        "TAB" => 0x09,
+        "ESC" | "ESCAPE" => 0x1B,
+        "BACKSPACE" => 0x08,
+        "INSERT" => 0x2D,
+        "DELETE" => 0x2E,
+        "HOME" => 0x24,
+        "END" => 0x23,
+        "PAGEUP" => 0x21,
+        "PAGEDOWN" => 0x22,
+        "LEFT" | "ARROWLEFT" => 0x25,
+        "UP" | "ARROWUP" => 0x26,
+        "RIGHT" | "ARROWRIGHT" => 0x27,
+        "DOWN" | "ARROWDOWN" => 0x28,
+        "`" | "BACKQUOTE" => 0xC0,
+        // virtual-key codes of letters and digits are their ascii codes
+        key if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) => key.as_bytes()[0] as i32,
+        key if key.starts_with('F') => match key[1..].parse::<i32>() {
+            Ok(n) if (1..=24).contains(&n) => 0x6F + n,
+            _ => return None
+        },
+        _ => return None
+    };
+    Some(code)
+}
+
+/// whether the key of virtual-key code is held down now
+#[cfg(target_os = "windows")]
+pub fn is_key_down(key_code: i32) -> bool {
+    let state = unsafe { GetAsyncKeyState(key_code) };
+    (state as u16 & 0x8000) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accelerator_key_code() {
+        assert_eq!(accelerator_key_code("F1"), Some(0x70));
+        assert_eq!(accelerator_key_code("CmdOrCtrl+Shift+F12"), Some(0x7B));
+        assert_eq!(accelerator_key_code("Alt+KeyR"), Some(0x52));
+        assert_eq!(accelerator_key_code("Ctrl+1"), Some(0x31));
+        assert_eq!(accelerator_key_code("Space"), Some(0x20));
+        assert_eq!(accelerator_key_code("F25"), None);
+        assert_eq!(accelerator_key_code("Ctrl+Plus"), None);
+    }
+}
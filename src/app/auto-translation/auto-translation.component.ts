@@ -1,8 +1,16 @@
-import {Component, OnInit} from '@angular/core';
-import {AutoTranslationConfig, TranslateByBaidu, Translator, TranslatorTypes} from './auto-translation';
+import {Component, NgZone, OnInit} from '@angular/core';
+import {
+  AutoTranslateTool,
+  AutoTranslationConfig,
+  GlossaryConfig,
+  GlossaryTerm,
+  TranslateByBaidu,
+  Translator,
+  TranslatorTypes
+} from './auto-translation';
 import {ActivatedRoute} from '@angular/router';
 import {AutoTranslationService} from './auto-translation.service';
-import {FormBuilder, FormControl, FormGroup} from '@angular/forms';
+import {FormArray, FormBuilder, FormControl, FormGroup, Validators} from '@angular/forms';
 import {debounceTime, filter} from 'rxjs';
 import {BaiduLanguages} from "./baidu_languages";
 
@@ -20,12 +28,14 @@ export class AutoTranslationComponent implements OnInit {
   baiduLanguages: { [key: string]: string } = {};
 
   configForm!: FormGroup;
+  glossaryForm?: FormGroup;
 
   appIdVisible = false;
   secretVisible = false;
 
   constructor(private activatedRoute: ActivatedRoute,
               private service: AutoTranslationService,
+              private ngZone: NgZone,
               private fb: FormBuilder) {
   }
 
@@ -35,20 +45,11 @@ export class AutoTranslationComponent implements OnInit {
         const configData = config as AutoTranslationConfig;
 
         this.configForm = this.fb.group({
-          enable: [configData.enable]
+          enable: [configData.enable],
+          timeout_ms: [configData.timeout_ms, [Validators.required, Validators.min(1)]],
+          fallbacks: this.fb.array((configData.fallbacks ?? []).map(tool => this.toolGroup(tool)))
         });
-
-        if (configData.tool.type === TranslatorTypes['Baidu'].type) {
-          const translateByBaidu = configData.tool as TranslateByBaidu;
-          this.configForm.addControl('tool', this.fb.group({
-            type: [translateByBaidu.type],
-            api_addr: [translateByBaidu.api_addr],
-            appId: [translateByBaidu.appId],
-            secret: [translateByBaidu.secret],
-            from: [translateByBaidu.from],
-            to: [translateByBaidu.to]
-          }));
-        }
+        this.configForm.addControl('tool', this.toolGroup(configData.tool));
 
         this.configForm.valueChanges
           .pipe(
@@ -60,6 +61,13 @@ export class AutoTranslationComponent implements OnInit {
             });
           });
       });
+    this.service.getTranslationGlossary().subscribe(glossary => {
+      this.ngZone.run(() => {
+        if (!!glossary) {
+          this.initGlossaryForm(glossary);
+        }
+      });
+    });
     BaiduLanguages.forEach((value, index) => {
       if (index > 0) {
         this.baiduToLanguageTypes.push(value.key);
@@ -69,6 +77,90 @@ export class AutoTranslationComponent implements OnInit {
     });
   }
 
+  private toolGroup(tool: AutoTranslateTool): FormGroup {
+    const translateByBaidu = tool as TranslateByBaidu;
+    return this.fb.group({
+      type: [translateByBaidu.type],
+      api_addr: [translateByBaidu.api_addr ?? ''],
+      appId: [translateByBaidu.appId ?? ''],
+      secret: [translateByBaidu.secret ?? ''],
+      from: [translateByBaidu.from ?? 'auto'],
+      to: [translateByBaidu.to ?? 'jp']
+    });
+  }
+
+  private initGlossaryForm(glossary: GlossaryConfig) {
+    this.glossaryForm = this.fb.group({
+      terms: this.fb.array((glossary.terms ?? []).map(term => this.termGroup(term))),
+      protected: this.fb.array((glossary.protected ?? []).map(word => this.fb.control(word, [Validators.required]))),
+    });
+
+    this.glossaryForm.valueChanges
+      .pipe(
+        filter(() => !!this.glossaryForm?.valid),
+        debounceTime(500),
+      )
+      .subscribe(value => {
+        this.service.saveTranslationGlossary(value as GlossaryConfig).subscribe(() => {
+        });
+      });
+  }
+
+  private termGroup(term: GlossaryTerm): FormGroup {
+    return this.fb.group({
+      source: [term.source ?? '', [Validators.required]],
+      target: [term.target ?? '', [Validators.required]],
+    });
+  }
+
+  addTerm(): void {
+    this.terms.push(this.termGroup(new GlossaryTerm()));
+  }
+
+  removeTerm(index: number): void {
+    this.terms.removeAt(index);
+  }
+
+  addProtected(): void {
+    this.protectedWords.push(this.fb.control('', [Validators.required]));
+  }
+
+  removeProtected(index: number): void {
+    this.protectedWords.removeAt(index);
+  }
+
+  get terms(): FormArray {
+    return this.glossaryForm?.get('terms') as FormArray;
+  }
+
+  termGroupAt(index: number): FormGroup {
+    return this.terms.at(index) as FormGroup;
+  }
+
+  get protectedWords(): FormArray {
+    return this.glossaryForm?.get('protected') as FormArray;
+  }
+
+  protectedAt(index: number): FormControl {
+    return this.protectedWords.at(index) as FormControl;
+  }
+
+  addFallback(): void {
+    this.fallbacks.push(this.toolGroup(new TranslateByBaidu()));
+  }
+
+  removeFallback(index: number): void {
+    this.fallbacks.removeAt(index);
+  }
+
+  get fallbacks(): FormArray {
+    return this.configForm.get('fallbacks') as FormArray;
+  }
+
+  fallbackGroup(index: number): FormGroup {
+    return this.fallbacks.at(index) as FormGroup;
+  }
+
   get enable(): FormControl {
     return this.configForm.get('enable') as FormControl;
   }
@@ -3,7 +3,7 @@ import {Observable} from 'rxjs';
 import {fromPromise} from 'rxjs/internal/observable/innerFrom';
 import {invoke} from '@tauri-apps/api';
 import {ActivatedRouteSnapshot, ResolveFn, RouterStateSnapshot} from '@angular/router';
-import {AutoTranslationConfig} from './auto-translation';
+import {AutoTranslationConfig, GlossaryConfig} from './auto-translation';
 
 @Injectable({
   providedIn: 'root'
@@ -19,6 +19,14 @@ export class AutoTranslationService {
   saveAutoTranslationConfig(config: AutoTranslationConfig): Observable<boolean> {
     return fromPromise<boolean>(invoke<boolean>('save_auto_translation_config', {config}));
   }
+
+  getTranslationGlossary(): Observable<GlossaryConfig | null> {
+    return fromPromise<GlossaryConfig | null>(invoke<GlossaryConfig | null>('get_translation_glossary'));
+  }
+
+  saveTranslationGlossary(config: GlossaryConfig): Observable<boolean> {
+    return fromPromise<boolean>(invoke<boolean>('save_translation_glossary', {config}));
+  }
 }
 
 export const autoTranslationConfigResolver: ResolveFn<AutoTranslationConfig> =
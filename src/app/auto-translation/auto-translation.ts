@@ -1,6 +1,19 @@
 export class AutoTranslationConfig {
   enable!: boolean;
   tool!: AutoTranslateTool;
+  fallbacks!: AutoTranslateTool[];
+  timeout_ms!: number;
+}
+
+export class GlossaryTerm {
+  source!: string;
+  target!: string;
+}
+
+export class GlossaryConfig {
+  terms!: GlossaryTerm[];
+  // terms which should be kept as is, like channel names
+  protected!: string[];
 }
 
 export abstract class AutoTranslateTool {
@@ -29,6 +29,8 @@ export class AudioSelectionConfig {
 export class StreamConfig {
   stream_input!: boolean;
   stream_mic_input!: boolean;
+  denoise_mic_input!: boolean;
+  agc_mic_input!: boolean;
 }
 
 export class AudioConfigResponseData {
@@ -1,8 +1,15 @@
 import {Component, NgZone, OnDestroy, OnInit} from '@angular/core';
-import {EngineTypes, VoiceEngineConfig, VoiceVoxConfigType, VoiceVoxEngineConfig} from './voice-engine';
+import {
+  EngineTypes,
+  KatakanaReading,
+  TextProcessingConfig,
+  VoiceEngineConfig,
+  VoiceVoxConfigType,
+  VoiceVoxEngineConfig
+} from './voice-engine';
 import {VoiceEngineService} from './voice-engine.service';
 import {ActivatedRoute} from '@angular/router';
-import {FormBuilder, FormControl, FormGroup} from '@angular/forms';
+import {FormArray, FormBuilder, FormControl, FormGroup, Validators} from '@angular/forms';
 import {NzNotificationService} from 'ng-zorro-antd/notification';
 import {debounceTime, filter, interval, Subject, takeUntil} from 'rxjs';
 
@@ -17,6 +24,7 @@ export class VoiceEngineComponent implements OnInit, OnDestroy {
   engineTypeValues = Object.keys(EngineTypes);
 
   voiceEngineConfigForm!: FormGroup;
+  textProcessingForm?: FormGroup;
   voicevoxEngineInitialized = false;
   voicevoxEngineLoading = false;
 
@@ -39,12 +47,25 @@ export class VoiceEngineComponent implements OnInit, OnDestroy {
       .subscribe(() => {
         this.checkVoicevoxEngineStatus();
       });
+    this.service.getTextProcessingConfig().subscribe(config => {
+      this.ngZone.run(() => {
+        if (!!config) {
+          this.initTextProcessingForm(config);
+        }
+      });
+    });
     this.activatedRoute.data.subscribe(
       ({config}) => {
         const engineConfig = config as VoiceEngineConfig;
         const voiceEngineConfig = engineConfig.config;
+        const loudness = engineConfig.loudness;
         this.voiceEngineConfigForm = this.fb.group({
-          type: [voiceEngineConfig.type]
+          type: [voiceEngineConfig.type],
+          loudness: this.fb.group({
+            enable: [loudness?.enable ?? false],
+            target_lufs: [loudness?.target_lufs ?? -16, [Validators.required, Validators.min(-70), Validators.max(-1)]],
+            peak_db: [loudness?.peak_db ?? -1, [Validators.required, Validators.max(0)]],
+          })
         });
 
         const configTypeControl = this.fb.control(voiceEngineConfig.type);
@@ -92,10 +113,82 @@ export class VoiceEngineComponent implements OnInit, OnDestroy {
     }));
   }
 
+  private initTextProcessingForm(textProcessing: TextProcessingConfig) {
+    const normalizer = textProcessing.normalizer;
+    const katakana = textProcessing.katakana;
+    this.textProcessingForm = this.fb.group({
+      normalizer: this.fb.group({
+        enable: [normalizer.enable],
+        strip_markdown: [normalizer.strip_markdown],
+        replace_urls: [normalizer.replace_urls],
+        replace_emoji: [normalizer.replace_emoji],
+        expand_abbreviations: [normalizer.expand_abbreviations],
+        expand_symbols: [normalizer.expand_symbols],
+        expand_numbers: [normalizer.expand_numbers],
+        replace_laugh: [normalizer.replace_laugh],
+        max_repeat: [normalizer.max_repeat, [Validators.required, Validators.min(0)]],
+      }),
+      katakana: this.fb.group({
+        enable: [katakana.enable],
+        overrides: this.fb.array((katakana.overrides ?? []).map(reading => this.katakanaReadingGroup(reading))),
+      }),
+    });
+
+    this.textProcessingForm.valueChanges
+      .pipe(
+        filter(() => !!this.textProcessingForm?.valid),
+        debounceTime(500),
+      )
+      .subscribe(value => {
+        // sections not shown here keep their values
+        const config = {...textProcessing, ...value} as TextProcessingConfig;
+        this.service.saveTextProcessingConfig(config).subscribe((ok) => {
+          if (!ok) {
+            this.notification.error('警告', '配置更新失败！')
+          }
+        });
+      });
+  }
+
+  private katakanaReadingGroup(reading: KatakanaReading): FormGroup {
+    return this.fb.group({
+      word: [reading.word ?? '', [Validators.required]],
+      reading: [reading.reading ?? '', [Validators.required]],
+    });
+  }
+
+  addKatakanaOverride(): void {
+    this.katakanaOverrides.push(this.katakanaReadingGroup(new KatakanaReading()));
+  }
+
+  removeKatakanaOverride(index: number): void {
+    this.katakanaOverrides.removeAt(index);
+  }
+
+  get katakanaEnable(): FormControl {
+    return this.textProcessingForm?.get('katakana')?.get('enable') as FormControl;
+  }
+
+  get katakanaOverrides(): FormArray {
+    return this.textProcessingForm?.get('katakana')?.get('overrides') as FormArray;
+  }
+
+  katakanaOverrideAt(index: number): FormGroup {
+    return this.katakanaOverrides.at(index) as FormGroup;
+  }
+
+  get normalizerEnable(): FormControl {
+    return this.textProcessingForm?.get('normalizer')?.get('enable') as FormControl;
+  }
+
   get config(): FormGroup {
     return this.voiceEngineConfigForm.get('config')?.get('config') as FormGroup;
   }
 
+  get loudnessEnable(): FormControl {
+    return this.voiceEngineConfigForm.get('loudness')?.get('enable') as FormControl;
+  }
+
   get type(): FormControl {
     return this.voiceEngineConfigForm.get('type') as FormControl;
   }
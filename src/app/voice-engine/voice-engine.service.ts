@@ -3,7 +3,7 @@ import {invoke} from '@tauri-apps/api';
 import {Observable, of} from 'rxjs';
 import {fromPromise} from 'rxjs/internal/observable/innerFrom';
 import {ResolveFn} from '@angular/router';
-import {VoiceEngineConfig} from './voice-engine';
+import {TextProcessingConfig, VoiceEngineConfig} from './voice-engine';
 import {VoiceVoxSpeaker, VoiceVoxSpeakerInfo} from './voice-vox-engine/voice-vox';
 
 @Injectable({
@@ -31,6 +31,14 @@ export class VoiceEngineService {
     return fromPromise<boolean>(invoke<boolean>('save_voice_engine_config', {config}));
   }
 
+  getTextProcessingConfig(): Observable<TextProcessingConfig | null> {
+    return fromPromise<TextProcessingConfig | null>(invoke<TextProcessingConfig | null>('get_text_processing_config'));
+  }
+
+  saveTextProcessingConfig(config: TextProcessingConfig): Observable<boolean> {
+    return fromPromise<boolean>(invoke<boolean>('save_text_processing_config', {config}));
+  }
+
   getVoiceVoxSpeakers(): Observable<VoiceVoxSpeaker[]> {
     return fromPromise<VoiceVoxSpeaker[]>(invoke<VoiceVoxSpeaker[]>('get_voice_vox_speakers'));
   }
@@ -24,7 +24,43 @@ export class VoiceEngineConfigDetail {
   config!: VoiceEngineConfigData;
 }
 
+// loudness normalization of generated audio
+export class LoudnessConfig {
+  enable!: boolean;
+  target_lufs!: number;
+  peak_db!: number;
+}
+
 export class VoiceEngineConfig {
   type!: string;
   config!: VoiceEngineConfigDetail;
+  loudness!: LoudnessConfig;
+}
+
+export class NormalizerConfig {
+  enable!: boolean;
+  strip_markdown!: boolean;
+  replace_urls!: boolean;
+  replace_emoji!: boolean;
+  expand_abbreviations!: boolean;
+  expand_symbols!: boolean;
+  expand_numbers!: boolean;
+  replace_laugh!: boolean;
+  // 0 means never collapse
+  max_repeat!: number;
+}
+
+export class KatakanaReading {
+  word!: string;
+  reading!: string;
+}
+
+export class KatakanaConfig {
+  enable!: boolean;
+  overrides!: KatakanaReading[];
+}
+
+export class TextProcessingConfig {
+  normalizer!: NormalizerConfig;
+  katakana!: KatakanaConfig;
 }
@@ -1,6 +1,20 @@
 import {Component, NgZone, OnDestroy, OnInit} from '@angular/core';
-import {FormBuilder, FormControl, FormGroup} from "@angular/forms";
-import {RecognizeByWhisper, RecognizerTypes, VoiceRecognitionConfig, WhisperConfigType} from "./voice-recognition";
+import {AbstractControl, FormBuilder, FormControl, FormGroup, ValidationErrors, Validators} from "@angular/forms";
+import {
+  MAX_VOICE_MS,
+  RecognizeByOpenAi,
+  RecognizeByVosk,
+  RecognizeByWhisper,
+  RecognizerTypes,
+  RecordModes,
+  TalkParams,
+  TranscriptionResponseFormats,
+  VadTypes,
+  VoiceRecognitionConfig,
+  WhisperConfigType,
+  WhisperSamplingStrategies,
+  WhisperTasks
+} from "./voice-recognition";
 import {ActivatedRoute} from "@angular/router";
 import {VoiceRecognitionService} from "./voice-recognition.service";
 import {debounceTime, filter} from "rxjs";
@@ -14,14 +28,26 @@ import {listen} from "@tauri-apps/api/event";
 })
 export class VoiceRecognitionComponent implements OnInit, OnDestroy {
   recognizerTypes = RecognizerTypes;
-  recognizers = Object.keys(RecognizerTypes);
+  recognizers: string[] = [];
+  recordModes = RecordModes;
+  recordModeTypes = Object.keys(RecordModes);
+  vadTypes = VadTypes;
+  vadTypeKeys = Object.keys(VadTypes);
+  maxVoiceMs = MAX_VOICE_MS;
   configForm!: FormGroup;
+  talkForm?: FormGroup;
 
   whisperLanguages: { [key: string]: string } = {};
   whisperLanguageTypes: string[] = [];
   whisperConfigTypes = WhisperConfigType;
+  whisperTasks = WhisperTasks;
+  whisperStrategies = WhisperSamplingStrategies;
+  whisperStrategyTypes = Object.keys(WhisperSamplingStrategies);
+  whisperTaskTypes = Object.keys(WhisperTasks);
   whisperModels = WhisperModels;
   whisperAvailableModels: { [key: string]: boolean } = {};
+  voskModels: string[] = [];
+  responseFormats = TranscriptionResponseFormats;
 
   private unListenWhisperModelLoad?: () => void;
 
@@ -38,6 +64,13 @@ export class VoiceRecognitionComponent implements OnInit, OnDestroy {
       ({config}) => {
         this.loadAndInitByConfig(config as VoiceRecognitionConfig);
       });
+    this.service.getTalkParams().subscribe(params => {
+      this.ngZone.run(() => {
+        if (!!params) {
+          this.initTalkForm(params);
+        }
+      });
+    });
     WhisperLanguages.forEach((value, _) => {
       this.whisperLanguages[value.key] = value.name;
       this.whisperLanguageTypes.push(value.key);
@@ -61,22 +94,58 @@ export class VoiceRecognitionComponent implements OnInit, OnDestroy {
       enable: [configData.enable],
       generate_after: [configData.generate_after],
       recordKey: [configData.recordKey],
+      record_mode: [configData.record_mode],
+      streaming: [configData.streaming],
     });
 
-    if (configData.tool.type === RecognizerTypes['Whisper'].type) {
-      const recognizeByWhisper = configData.tool as RecognizeByWhisper;
-      // translate null to auto
-      if (recognizeByWhisper.language === null) {
-        recognizeByWhisper.language = 'auto';
-      }
-      this.configForm.addControl('tool', this.fb.group({
-        type: [recognizeByWhisper.type],
-        api_addr: [recognizeByWhisper.api_addr],
-        config_type: [recognizeByWhisper.config_type],
-        use_model: [recognizeByWhisper.use_model],
-        language: [recognizeByWhisper.language]
-      }));
+    // every recognizer has its own group, only the group of chosen type is enabled and saved
+    const toolType = configData.tool.type;
+    this.recognizers = Object.keys(RecognizerTypes);
+    const recognizeByWhisper = (toolType === RecognizerTypes['Whisper'].type
+      ? configData.tool : new RecognizeByWhisper()) as RecognizeByWhisper;
+    // translate null to auto
+    if (!recognizeByWhisper.language) {
+      recognizeByWhisper.language = 'auto';
     }
+    const recognizeByVosk = (toolType === RecognizerTypes['Vosk'].type
+      ? configData.tool : new RecognizeByVosk()) as RecognizeByVosk;
+    this.loadVoskModels(recognizeByVosk.model);
+    const recognizeByOpenAi = (toolType === RecognizerTypes['OpenAi'].type
+      ? configData.tool : new RecognizeByOpenAi()) as RecognizeByOpenAi;
+    this.configForm.addControl('tool', this.fb.group({
+      type: [toolType],
+      Whisper: this.fb.group({
+        api_addr: [recognizeByWhisper.api_addr ?? ''],
+        config_type: [recognizeByWhisper.config_type ?? WhisperConfigType.HTTP],
+        use_model: [recognizeByWhisper.use_model ?? 'base'],
+        language: [recognizeByWhisper.language],
+        decode: this.fb.group({
+          strategy: [recognizeByWhisper.decode?.strategy ?? null],
+          beam_size: [recognizeByWhisper.decode?.beam_size ?? null, [Validators.min(1)]],
+          n_threads: [recognizeByWhisper.decode?.n_threads ?? null, [Validators.min(1)]],
+          audio_ctx: [recognizeByWhisper.decode?.audio_ctx ?? null, [Validators.min(64), Validators.max(1500)]],
+        }),
+        initial_prompt: [recognizeByWhisper.initial_prompt ?? null],
+        vocabulary: [recognizeByWhisper.vocabulary ?? []],
+        task: [recognizeByWhisper.task ?? 'Transcribe'],
+        skip_translation: [recognizeByWhisper.skip_translation ?? false],
+      }),
+      Vosk: this.fb.group({
+        model: [recognizeByVosk.model ?? null, [Validators.required]],
+        library_path: [recognizeByVosk.library_path ?? null],
+      }),
+      OpenAi: this.fb.group({
+        api_addr: [recognizeByOpenAi.api_addr ?? '', [Validators.required]],
+        api_key: [recognizeByOpenAi.api_key ?? null],
+        model: [recognizeByOpenAi.model ?? 'whisper-1', [Validators.required]],
+        language: [recognizeByOpenAi.language || 'auto'],
+        prompt: [recognizeByOpenAi.prompt ?? null],
+        temperature: [recognizeByOpenAi.temperature ?? 0, [Validators.required, Validators.min(0), Validators.max(1)]],
+        response_format: [recognizeByOpenAi.response_format ?? 'json'],
+      }),
+    }));
+    this.enableToolGroup(toolType);
+    this.type.valueChanges.subscribe(type => this.enableToolGroup(type));
 
     this.configForm.valueChanges
       .pipe(
@@ -84,17 +153,82 @@ export class VoiceRecognitionComponent implements OnInit, OnDestroy {
         debounceTime(500),
       )
       .subscribe(value => {
-        if (value.tool.type === RecognizerTypes['Whisper'].type) {
-          // translate auto to null
-          if (value.tool.language === 'auto') {
-            value.tool.language = null;
-          }
+        const toolConfig = {...value.tool[value.tool.type]};
+        // translate auto to null
+        if (toolConfig.language === 'auto') {
+          toolConfig.language = null;
         }
+        value.tool = {type: value.tool.type, ...toolConfig};
         this.service.saveVoiceRecognitionConfig(value).subscribe(() => {
         });
       });
   }
 
+  private enableToolGroup(type: string) {
+    const tool = this.configForm.get('tool') as FormGroup;
+    Object.keys(RecognizerTypes).forEach(key => {
+      const group = tool.get(key);
+      if (!group) {
+        return;
+      }
+      if (key === type) {
+        group.enable({emitEvent: false});
+      } else {
+        group.disable({emitEvent: false});
+      }
+    });
+  }
+
+  private loadVoskModels(current?: string) {
+    this.service.getVoskAvailableModels().subscribe(value => {
+      this.ngZone.run(() => {
+        const models = value ?? [];
+        // a model given by absolute path is not listed in vosk/models
+        if (!!current && !models.includes(current)) {
+          models.push(current);
+        }
+        this.voskModels = models;
+      });
+    });
+  }
+
+  private initTalkForm(params: TalkParams) {
+    this.talkForm = this.fb.group({
+      vad_thold: [params.vad_thold, [Validators.required, Validators.min(0)]],
+      freq_thold: [params.freq_thold, [Validators.required, Validators.min(0)]],
+      voice_ms: [params.voice_ms, [Validators.required, Validators.min(1), Validators.max(MAX_VOICE_MS)]],
+      step_ms: [params.step_ms, [Validators.required, Validators.min(1)]],
+      probe_ms: [params.probe_ms, [Validators.required, Validators.min(1)]],
+      tail_ms: [params.tail_ms, [Validators.required, Validators.min(1)]],
+      pre_roll_ms: [params.pre_roll_ms, [Validators.required, Validators.min(0)]],
+      hangover_ms: [params.hangover_ms, [Validators.required, Validators.min(0)]],
+      min_speech_ms: [params.min_speech_ms, [Validators.required, Validators.min(0)]],
+      denoise: [params.denoise],
+      agc: [params.agc],
+      vad: this.fb.group({
+        type: [params.vad.type],
+        model_path: [params.vad.model_path ?? null],
+        library_path: [params.vad.library_path ?? null],
+        threshold: [params.vad.threshold ?? 0.5, [Validators.min(0), Validators.max(1)]],
+      }),
+    }, {validators: tailShorterThanProbe});
+
+    this.talkForm.valueChanges
+      .pipe(
+        filter(() => !!this.talkForm?.valid),
+        debounceTime(500),
+      )
+      .subscribe(value => {
+        // fields not shown here like verbose keep their values
+        const talkParams = {...params, ...value} as TalkParams;
+        if (talkParams.vad.type !== 'Silero') {
+          talkParams.vad = {type: talkParams.vad.type};
+        }
+        this.service.saveTalkParams(talkParams).subscribe(() => {
+        });
+      });
+  }
+
   private loadWhisperAvailableModel() {
     this.service.getWhisperAvailableModels().subscribe(value => {
       this.ngZone.run(() => {
@@ -118,10 +252,29 @@ export class VoiceRecognitionComponent implements OnInit, OnDestroy {
   }
 
   get whisperConfigType(): FormControl {
-    return this.configForm.get('tool')?.get('config_type') as FormControl;
+    return this.configForm.get('tool')?.get('Whisper')?.get('config_type') as FormControl;
   }
 
   get whisperUseModel(): FormControl {
-    return this.configForm.get('tool')?.get('use_model') as FormControl;
+    return this.configForm.get('tool')?.get('Whisper')?.get('use_model') as FormControl;
   }
+
+  get whisperStrategy(): FormControl {
+    return this.configForm.get('tool')?.get('Whisper')?.get('decode')?.get('strategy') as FormControl;
+  }
+
+  get whisperTask(): FormControl {
+    return this.configForm.get('tool')?.get('Whisper')?.get('task') as FormControl;
+  }
+
+  get vadType(): FormControl | undefined {
+    return this.talkForm?.get('vad')?.get('type') as FormControl;
+  }
+}
+
+// the tail of probe window should be shorter than the window, see TalkParams of backend
+function tailShorterThanProbe(group: AbstractControl): ValidationErrors | null {
+  const probe = group.get('probe_ms')?.value;
+  const tail = group.get('tail_ms')?.value;
+  return tail < probe ? null : {tailNotShorterThanProbe: true};
 }
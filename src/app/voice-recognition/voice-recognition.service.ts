@@ -3,7 +3,7 @@ import {invoke} from '@tauri-apps/api';
 import {Observable} from 'rxjs';
 import {fromPromise} from 'rxjs/internal/observable/innerFrom';
 import {ResolveFn} from '@angular/router';
-import {VoiceRecognitionConfig} from "./voice-recognition";
+import {TalkParams, VoiceRecognitionConfig} from "./voice-recognition";
 
 @Injectable({
   providedIn: 'root'
@@ -21,6 +21,14 @@ export class VoiceRecognitionService {
     return fromPromise<boolean>(invoke<boolean>('save_voice_recognition_config', {config}));
   }
 
+  getTalkParams(): Observable<TalkParams> {
+    return fromPromise<TalkParams>(invoke<TalkParams>('get_talk_params'));
+  }
+
+  saveTalkParams(params: TalkParams): Observable<boolean> {
+    return fromPromise<boolean>(invoke<boolean>('save_talk_params', {params}));
+  }
+
   isRecorderRecording(): Observable<boolean> {
     return fromPromise<boolean>(invoke<boolean>('is_recorder_recording'));
   }
@@ -28,6 +36,10 @@ export class VoiceRecognitionService {
   getWhisperAvailableModels(): Observable<string[]> {
     return fromPromise<string[]>(invoke<string[]>('whisper_available_models'));
   }
+
+  getVoskAvailableModels(): Observable<string[] | null> {
+    return fromPromise<string[] | null>(invoke<string[] | null>('vosk_available_models'));
+  }
 }
 
 export const voiceRecognitionConfigResolver: ResolveFn<VoiceRecognitionConfig> =
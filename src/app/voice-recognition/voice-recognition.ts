@@ -2,9 +2,49 @@ export class VoiceRecognitionConfig {
   enable!: boolean;
   generate_after!: boolean;
   recordKey!: string;
+  record_mode!: string;
   tool!: RecognitionTool;
+  streaming!: boolean;
+  talk!: TalkParams;
 }
 
+export const RecordModes: { [key: string]: string } = {
+  Toggle: '按键开关',
+  PushToTalk: '按住说话',
+  AlwaysOn: '始终开启'
+};
+
+export class TalkParams {
+  vad_thold!: number;
+  freq_thold!: number;
+  voice_ms!: number;
+  step_ms!: number;
+  probe_ms!: number;
+  tail_ms!: number;
+  pre_roll_ms!: number;
+  hangover_ms!: number;
+  min_speech_ms!: number;
+  denoise!: boolean;
+  agc!: boolean;
+  verbose!: boolean;
+  vad!: VadConfig;
+}
+
+export class VadConfig {
+  type!: string;
+  model_path?: string | null;
+  library_path?: string | null;
+  threshold?: number;
+}
+
+export const VadTypes: { [key: string]: string } = {
+  Energy: '能量',
+  Silero: 'Silero'
+};
+
+// audio buffer of listener keeps 30000ms at most
+export const MAX_VOICE_MS = 30000;
+
 export abstract class RecognitionTool {
   type!: string;
 }
@@ -20,7 +60,9 @@ export class Recognizer {
 }
 
 export const RecognizerTypes: { [key: string]: Recognizer } = {
-  Whisper: new Recognizer("Whisper", "Whisper")
+  Whisper: new Recognizer("Whisper", "Whisper"),
+  OpenAi: new Recognizer("OpenAi", "OpenAI 兼容接口"),
+  Vosk: new Recognizer("Vosk", "Vosk")
 };
 
 export const WhisperConfigType = {
@@ -28,14 +70,72 @@ export const WhisperConfigType = {
   BINARY: 'Binary'
 };
 
+export const WhisperSamplingStrategies: { [key: string]: string } = {
+  Greedy: '贪心',
+  BeamSearch: '束搜索'
+};
+
+// decoding params of whisper.cpp library, params not set fall back to the built-in values
+export class WhisperDecodeParams {
+  strategy?: string | null;
+  beam_size?: number | null;
+  n_threads?: number | null;
+  // should be multiple of 64, 1500 means full 30s audio context
+  audio_ctx?: number | null;
+}
+
+export const WhisperTasks: { [key: string]: string } = {
+  Transcribe: '识别原语言',
+  Translate: '翻译为英文'
+};
+
 export class RecognizeByWhisper extends RecognitionTool {
   config_type!: string;
   use_model!: string;
   api_addr!: string;
   language?: string | null;
+  // only used by binary config type
+  decode?: WhisperDecodeParams;
+  // text to condition the decoder on, like a previous sentence in the expected style
+  initial_prompt?: string | null;
+  // domain terms and speaker names whisper should prefer spelling as is
+  vocabulary?: string[];
+  task?: string;
+  // generate audio of the english text as is without the auto translation, only for translate task
+  skip_translation?: boolean;
 
   constructor() {
     super();
     this.type = RecognizerTypes['Whisper'].type;
   }
 }
+
+// response formats of openai compatible transcription api
+export const TranscriptionResponseFormats = ['json', 'verbose_json', 'text'];
+
+export class RecognizeByOpenAi extends RecognitionTool {
+  // base address without `/v1`, e.g. http://127.0.0.1:8000
+  api_addr!: string;
+  api_key?: string | null;
+  model!: string;
+  language?: string | null;
+  prompt?: string | null;
+  temperature!: number;
+  response_format!: string;
+
+  constructor() {
+    super();
+    this.type = RecognizerTypes['OpenAi'].type;
+  }
+}
+
+export class RecognizeByVosk extends RecognitionTool {
+  // model directory name inside `vosk/models`, or an absolute path of model directory
+  model!: string;
+  library_path?: string | null;
+
+  constructor() {
+    super();
+    this.type = RecognizerTypes['Vosk'].type;
+  }
+}